@@ -1,36 +1,73 @@
 pub use self::error::EmitError;
 use self::funcs::escape_str;
 use self::funcs::need_quotes;
+use self::writer::Writer;
+use crate::yaml::CollectionFormat;
 use crate::yaml::Hash;
 use crate::yaml::IntegerFormat;
 use crate::yaml::Meta;
 use crate::yaml::StringFormat;
 use crate::yaml::Yaml;
 use std::fmt;
+use std::fmt::Write;
+use std::io;
+use std::iter;
 
 mod error;
 mod funcs;
+mod writer;
 
 pub struct YamlEmitter<'a> {
-    writer: &'a mut dyn fmt::Write,
+    writer: Writer<'a>,
     best_indent: usize,
     compact: bool,
+    indentless_sequences: bool,
     level: isize,
     intformat: IntegerFormat,
     strformat: StringFormat,
+    collection_format: CollectionFormat,
+    line_width: usize,
+    // number of flow collections the node being emitted is in
+    flow_level: usize,
 }
 
 pub type EmitResult = Result<(), EmitError>;
 
 impl<'a> YamlEmitter<'a> {
     pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter {
+        YamlEmitter::with_writer(Writer::fmt(writer))
+    }
+
+    /// Emit to a byte stream such as a file or a socket, through a buffer
+    /// flushed after each document. Errors of the stream are reported as
+    /// `EmitError::Io`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::{YamlEmitter, YamlLoader};
+    ///
+    /// let docs = YamlLoader::load_from_str("a: [1, 2]").unwrap();
+    /// let mut out = Vec::new();
+    /// YamlEmitter::from_io_writer(&mut out).dump(&docs[0]).unwrap();
+    /// assert_eq!(out, b"---\na:\n  - 1\n  - 2");
+    /// ```
+    pub fn from_io_writer(writer: &'a mut dyn io::Write) -> YamlEmitter<'a> {
+        YamlEmitter::with_writer(Writer::io(writer))
+    }
+
+    fn with_writer(writer: Writer<'a>) -> YamlEmitter<'a> {
         YamlEmitter {
             writer,
             best_indent: 2,
             compact: true,
+            indentless_sequences: false,
             level: -1,
             intformat: IntegerFormat::Decimal,
             strformat: StringFormat::Standard,
+            collection_format: CollectionFormat::Block,
+            line_width: 80,
+            flow_level: 0,
         }
     }
 
@@ -40,8 +77,7 @@ impl<'a> YamlEmitter<'a> {
     /// [mappings](http://www.yaml.org/spec/1.2/spec.html#id2798057).
     ///
     /// In this form, blocks cannot have any properties (such as anchors
-    /// or tags), which should be OK, because this emitter doesn't
-    /// (currently) emit those anyways.
+    /// or tags), so collections carrying them always start on a new line.
     pub fn compact(&mut self, compact: bool) {
         self.compact = compact;
     }
@@ -51,9 +87,136 @@ impl<'a> YamlEmitter<'a> {
         self.compact
     }
 
+    /// Set the number of spaces indenting each level of block collections
+    /// and block scalars. Defaults to 2.
+    ///
+    /// # Panics
+    ///
+    /// If `indent` is not between 2 and 9, the widths allowed by the spec.
+    pub fn indent(&mut self, indent: usize) {
+        assert!(
+            (2..=9).contains(&indent),
+            "indentation must be between 2 and 9, got {}",
+            indent
+        );
+        self.best_indent = indent;
+    }
+
+    /// Get the number of spaces indenting each level.
+    pub fn get_indent(&self) -> usize {
+        self.best_indent
+    }
+
+    /// Emit block sequences held by mappings at the indentation of their key
+    /// (`key:\n- a`) instead of one level deeper (`key:\n  - a`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::{YamlEmitter, YamlLoader};
+    ///
+    /// let docs = YamlLoader::load_from_str("a: [1, {b: 2, c: 3}]").unwrap();
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.indent(4);
+    /// emitter.indentless_sequences(true);
+    /// emitter.dump(&docs[0]).unwrap();
+    /// assert_eq!(out, "---\na:\n- 1\n-   b: 2\n    c: 3");
+    /// ```
+    pub fn indentless_sequences(&mut self, indentless_sequences: bool) {
+        self.indentless_sequences = indentless_sequences;
+    }
+
+    /// Determine if block sequences held by mappings are indentless.
+    pub fn has_indentless_sequences(&self) -> bool {
+        self.indentless_sequences
+    }
+
+    /// Set the style of collections, `CollectionFormat::Block` by default.
+    /// It can be changed for a node and the nodes it holds by wrapping it in
+    /// `Meta::Collection`.
+    ///
+    /// Collections holding comments are emitted in block style, as flow
+    /// collections leave no room for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::yaml::{CollectionFormat, Meta};
+    /// use yaml_rust::{Yaml, YamlEmitter};
+    ///
+    /// let ports = vec![Yaml::Integer(80), Yaml::Integer(443)];
+    /// let ports = Meta::Collection(CollectionFormat::Flow, Box::new(Yaml::Array(ports)));
+    /// let mut hash = yaml_rust::yaml::Hash::new();
+    /// hash.insert(Yaml::String("ports".to_owned()), Yaml::Meta(ports));
+    ///
+    /// let mut out = String::new();
+    /// YamlEmitter::new(&mut out).dump(&Yaml::Hash(hash)).unwrap();
+    /// assert_eq!(out, "---\nports: [80, 443]");
+    /// ```
+    pub fn collection_format(&mut self, format: CollectionFormat) {
+        self.collection_format = format;
+    }
+
+    /// Get the style of collections not wrapped in `Meta::Collection`.
+    pub fn get_collection_format(&self) -> CollectionFormat {
+        self.collection_format
+    }
+
+    /// Set the number of columns collections in `CollectionFormat::Auto` are
+    /// emitted in flow style within. Defaults to 80.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::yaml::CollectionFormat;
+    /// use yaml_rust::{YamlEmitter, YamlLoader};
+    ///
+    /// let s = "ports: [80, 443]\nhosts: [a.example.com, b.example.com]";
+    /// let docs = YamlLoader::load_from_str(s).unwrap();
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.collection_format(CollectionFormat::Auto);
+    /// emitter.line_width(30);
+    /// emitter.dump(&docs[0]).unwrap();
+    /// assert_eq!(
+    ///     out,
+    ///     "---\nports: [80, 443]\nhosts:\n  - a.example.com\n  - b.example.com"
+    /// );
+    /// ```
+    pub fn line_width(&mut self, line_width: usize) {
+        self.line_width = line_width;
+    }
+
+    /// Get the number of columns collections in `CollectionFormat::Auto` are
+    /// emitted in flow style within.
+    pub fn get_line_width(&self) -> usize {
+        self.line_width
+    }
+
     pub fn dump(&mut self, doc: &'a Yaml) -> EmitResult {
+        let result = self.emit_document(doc);
+        self.writer.finish(result)
+    }
+
+    fn emit_document(&mut self, mut doc: &'a Yaml) -> EmitResult {
         write!(self.writer, "---")?;
 
+        // Properties of the root node are emitted after the document marker
+        loop {
+            doc = match *doc {
+                Yaml::Anchor(ref name, ref node) => {
+                    write!(self.writer, " &{}", name)?;
+                    node
+                }
+                Yaml::Tagged(ref tag, ref node) => {
+                    write!(self.writer, " {}", tag)?;
+                    node
+                }
+                _ => break,
+            };
+        }
+
         // Emits comments inlined after document beginning
         if let Yaml::Array(arr) = doc {
             if let Some(first) = arr.first() {
@@ -77,8 +240,21 @@ impl<'a> YamlEmitter<'a> {
 
     fn emit_node(&mut self, node: &'a Yaml) -> EmitResult {
         match *node {
-            Yaml::Array(ref v) => self.emit_array(v),
-            Yaml::Hash(ref v) => self.emit_hash(v),
+            Yaml::Array(ref v) => {
+                if self.is_flow(v.iter(), |emitter| emitter.emit_flow_array(v)) {
+                    return self.emit_flow_array(v);
+                }
+                self.emit_array(v)
+            }
+            Yaml::Hash(ref v) => {
+                let nodes = v
+                    .iter()
+                    .flat_map(|(k, v)| iter::once(k).chain(iter::once(v)));
+                if self.is_flow(nodes, |emitter| emitter.emit_flow_hash(v)) {
+                    return self.emit_flow_hash(v);
+                }
+                self.emit_hash(v)
+            }
             Yaml::String(ref v) => self.emit_string(v.as_str()),
             Yaml::Boolean(v) => {
                 match v {
@@ -103,7 +279,18 @@ impl<'a> YamlEmitter<'a> {
                 write!(self.writer, "~")?;
                 Ok(())
             }
-            Yaml::Alias(_) => Ok(()),
+            Yaml::Anchor(ref name, ref node) => {
+                write!(self.writer, "&{} ", name)?;
+                self.emit_node(node)
+            }
+            Yaml::Alias(ref name) => {
+                write!(self.writer, "*{}", name)?;
+                Ok(())
+            }
+            Yaml::Tagged(ref tag, ref node) => {
+                write!(self.writer, "{} ", tag)?;
+                self.emit_node(node)
+            }
             Yaml::Meta(ref op) => self.process_meta(op),
         }
     }
@@ -124,6 +311,13 @@ impl<'a> YamlEmitter<'a> {
                 self.strformat = old;
                 res
             }
+            Meta::Collection(f, node) => {
+                let old = self.collection_format;
+                self.collection_format = *f;
+                let res = self.emit_node(node);
+                self.collection_format = old;
+                res
+            }
         }
     }
 
@@ -131,12 +325,16 @@ impl<'a> YamlEmitter<'a> {
         match self.strformat {
             StringFormat::Standard => {
                 if need_quotes(value) {
-                    escape_str(self.writer, value, true)?;
+                    escape_str(&mut self.writer, value, true)?;
                 } else {
                     write!(self.writer, "{}", value)?;
                 }
             }
-            StringFormat::Quoted => escape_str(self.writer, value, true)?,
+            StringFormat::Quoted => escape_str(&mut self.writer, value, true)?,
+            // Flow collections are on one line, see `StringFormat::Quoted`
+            StringFormat::Block if self.flow_level > 0 => {
+                escape_str(&mut self.writer, value, true)?
+            }
             StringFormat::Block => {
                 if value.ends_with('\n') {
                     writeln!(self.writer, "|+")?;
@@ -151,7 +349,7 @@ impl<'a> YamlEmitter<'a> {
                     }
                     if !line.is_empty() {
                         self.emit_indent()?;
-                        escape_str(self.writer, line, false)?;
+                        escape_str(&mut self.writer, line, false)?;
                     }
                 }
                 self.level -= 1;
@@ -278,15 +476,14 @@ impl<'a> YamlEmitter<'a> {
                 continue;
             }
 
-            let is_complex_key = matches!(*key, Yaml::Hash(_) | Yaml::Array(_));
-            if is_complex_key {
+            if is_complex_key(key) {
                 write!(self.writer, "?")?;
                 self.emit_value(true, key)?;
                 self.emit_line_begin()?;
                 write!(self.writer, ":")?;
                 self.emit_value(true, value)?;
             } else {
-                self.emit_node(key)?;
+                self.emit_key(key, value)?;
                 write!(self.writer, ":")?;
                 self.emit_value(false, value)?;
             }
@@ -299,17 +496,141 @@ impl<'a> YamlEmitter<'a> {
         Ok(())
     }
 
+    /// Determine if the collection holding `nodes` is emitted in flow style,
+    /// `emit_flow` emitting it so from the current column.
+    fn is_flow<I, F>(&mut self, nodes: I, emit_flow: F) -> bool
+    where
+        I: Iterator<Item = &'a Yaml>,
+        F: FnOnce(&mut Self) -> EmitResult,
+    {
+        if self.flow_level > 0 {
+            return true;
+        }
+        // The leading inline comment belongs to the parent and is emitted
+        // after the collection then
+        let mut nodes = nodes.peekable();
+        nodes.next_if(|node| node.is_inline_comment());
+        match self.collection_format {
+            CollectionFormat::Block => false,
+            CollectionFormat::Flow => !nodes.any(holds_comments),
+            CollectionFormat::Auto => nodes.all(is_flow_scalar) && self.fits(emit_flow),
+        }
+    }
+
+    /// Determine if `emit` writes a single line within the line width.
+    fn fits<F: FnOnce(&mut Self) -> EmitResult>(&mut self, emit: F) -> bool {
+        let flow_level = self.flow_level;
+        let column = self.writer.measure(self.line_width);
+        let fits = emit(self).is_ok();
+        self.writer.stop_measuring(column);
+        self.flow_level = flow_level;
+        fits
+    }
+
+    fn emit_flow_array(&mut self, arr: &'a [Yaml]) -> EmitResult {
+        self.flow_level += 1;
+        write!(self.writer, "[")?;
+        let entries = arr.iter().filter(|entry| !entry.is_comment());
+        for (idx, entry) in entries.enumerate() {
+            if idx > 0 {
+                write!(self.writer, ", ")?;
+            }
+            self.emit_node(entry)?;
+        }
+        write!(self.writer, "]")?;
+        self.flow_level -= 1;
+        Ok(())
+    }
+
+    fn emit_flow_hash(&mut self, hash: &'a Hash) -> EmitResult {
+        self.flow_level += 1;
+        write!(self.writer, "{{")?;
+        let entries = hash.iter().filter(|(key, _)| !key.is_comment());
+        for (idx, (key, value)) in entries.enumerate() {
+            if idx > 0 {
+                write!(self.writer, ", ")?;
+            }
+            if is_complex_key(key) {
+                write!(self.writer, "? ")?;
+            }
+            self.emit_key(key, value)?;
+            write!(self.writer, ": ")?;
+            self.emit_node(value)?;
+        }
+        write!(self.writer, "}}")?;
+        self.flow_level -= 1;
+        Ok(())
+    }
+
+    fn emit_key(&mut self, key: &'a Yaml, value: &'a Yaml) -> EmitResult {
+        // Merge keys are plain, as any other `<<` key is quoted
+        if is_merge_key(key, value) {
+            write!(self.writer, "<<")?;
+            return Ok(());
+        }
+        self.emit_node(key)?;
+        // Anchor names may contain ':', keep it apart from the alias
+        if let Yaml::Alias(_) = *key {
+            write!(self.writer, " ")?;
+        }
+        Ok(())
+    }
+
     /// Emit a yaml as a hash or array value: i.e., which should appear
     /// following a ":" or "-", either after a space, or on a new line.
     /// If `inline` is true, then the preceding characters are distinct
     /// and short enough to respect the compact flag.
     fn emit_value(&mut self, inline: bool, value: &'a Yaml) -> EmitResult {
+        // Entries of a sequence held by a sequence would be read as entries
+        // of the outer one if not indented, only mappings can hold indentless
+        // sequences
+        let indentless = !inline && self.indentless_sequences;
+
+        let format = self.collection_format;
+        let res = self.emit_value_node(inline, indentless, value);
+        self.collection_format = format;
+        res
+    }
+
+    fn emit_value_node(
+        &mut self,
+        mut inline: bool,
+        indentless: bool,
+        mut value: &'a Yaml,
+    ) -> EmitResult {
+        // Properties are emitted on the line of the key or entry, the node
+        // cannot be compact then
+        loop {
+            value = match *value {
+                Yaml::Anchor(ref name, ref node) => {
+                    write!(self.writer, " &{}", name)?;
+                    inline = false;
+                    node
+                }
+                Yaml::Tagged(ref tag, ref node) => {
+                    write!(self.writer, " {}", tag)?;
+                    inline = false;
+                    node
+                }
+                Yaml::Meta(Meta::Collection(format, ref node)) => {
+                    self.collection_format = format;
+                    node
+                }
+                _ => break,
+            };
+        }
+
         match *value {
             Yaml::Array(ref arr) => {
                 if arr.is_empty() {
                     write!(self.writer, " []")?;
                     return Ok(());
                 }
+                if self.is_flow(arr.iter(), |emitter| {
+                    emitter.emit_flow_value(value, &arr[0])
+                }) {
+                    return self.emit_flow_value(value, &arr[0]);
+                }
 
                 // Emit inlined comment before starting to spit out the array
                 // If the first entry is an inlined comment, it belongs to
@@ -320,6 +641,15 @@ impl<'a> YamlEmitter<'a> {
                     from = 1;
                 }
 
+                if indentless {
+                    writeln!(self.writer)?;
+                    self.emit_indent()?;
+                    self.level -= 1;
+                    let res = self.emit_array(&arr[from..]);
+                    self.level += 1;
+                    return res;
+                }
+
                 self.emit_value_indent(inline)?;
                 self.emit_array(&arr[from..])
             }
@@ -328,6 +658,13 @@ impl<'a> YamlEmitter<'a> {
                     self.writer.write_str(" {}")?;
                     return Ok(());
                 }
+                let first = hash.front().unwrap().0;
+                let nodes = hash
+                    .iter()
+                    .flat_map(|(k, v)| iter::once(k).chain(iter::once(v)));
+                if self.is_flow(nodes, |emitter| emitter.emit_flow_value(value, first)) {
+                    return self.emit_flow_value(value, first);
+                }
 
                 // Emit inlined comment before starting to spit out the hash
                 // If the first entry is an inlined comment, it belongs to
@@ -351,6 +688,21 @@ impl<'a> YamlEmitter<'a> {
         }
     }
 
+    // `first` being the first entry of the collection, possibly the inline
+    // comment of its parent
+    fn emit_flow_value(&mut self, value: &'a Yaml, first: &'a Yaml) -> EmitResult {
+        write!(self.writer, " ")?;
+        match *value {
+            Yaml::Array(ref arr) => self.emit_flow_array(arr)?,
+            Yaml::Hash(ref hash) => self.emit_flow_hash(hash)?,
+            _ => unreachable!("not a collection: {:?}", value),
+        }
+        if first.is_inline_comment() {
+            self.emit_node(first)?;
+        }
+        Ok(())
+    }
+
     fn emit_line_begin(&mut self) -> EmitResult {
         writeln!(self.writer)?;
         self.emit_indent()?;
@@ -359,7 +711,10 @@ impl<'a> YamlEmitter<'a> {
 
     fn emit_value_indent(&mut self, inline: bool) -> EmitResult {
         if inline && self.compact {
-            write!(self.writer, " ")?;
+            // Line up the node with the next lines of its collection
+            for _ in 1..self.best_indent {
+                write!(self.writer, " ")?;
+            }
         } else {
             writeln!(self.writer)?;
             self.level += 1;
@@ -377,9 +732,65 @@ impl<'a> YamlEmitter<'a> {
     }
 }
 
+fn is_complex_key(key: &Yaml) -> bool {
+    match *key {
+        Yaml::Hash(_) | Yaml::Array(_) => true,
+        Yaml::Anchor(_, ref node)
+        | Yaml::Tagged(_, ref node)
+        | Yaml::Meta(Meta::Collection(_, ref node)) => is_complex_key(node),
+        _ => false,
+    }
+}
+
+// `<<` tagged `!!merge`, as loaded by `YamlLoader` when merge keys are kept,
+// or `<<` merging aliases, as loaded when anchors are kept but merge keys are
+// not resolved
+fn is_merge_key(key: &Yaml, value: &Yaml) -> bool {
+    let is_alias = |node: &Yaml| matches!(*node, Yaml::Alias(_));
+    match (key, value) {
+        (Yaml::Tagged(ref tag, ref node), _) => tag == "!!merge" && node.as_str() == Some("<<"),
+        (Yaml::String(ref key), Yaml::Alias(_)) => key == "<<",
+        (Yaml::String(ref key), Yaml::Array(ref v)) => {
+            key == "<<" && !v.is_empty() && v.iter().all(is_alias)
+        }
+        _ => false,
+    }
+}
+
+// Scalars emitted in flow style as they are, unlike block scalars
+fn is_flow_scalar(node: &Yaml) -> bool {
+    match *node {
+        Yaml::Array(_) | Yaml::Hash(_) | Yaml::Comment(..) => false,
+        Yaml::Meta(Meta::String(StringFormat::Block, _)) => false,
+        Yaml::Anchor(_, ref node)
+        | Yaml::Tagged(_, ref node)
+        | Yaml::Meta(Meta::Integer(_, ref node))
+        | Yaml::Meta(Meta::String(_, ref node))
+        | Yaml::Meta(Meta::Collection(_, ref node)) => is_flow_scalar(node),
+        _ => true,
+    }
+}
+
+fn holds_comments(node: &Yaml) -> bool {
+    match *node {
+        Yaml::Comment(..) => true,
+        Yaml::Array(ref v) => v.iter().any(holds_comments),
+        Yaml::Hash(ref h) => h
+            .iter()
+            .any(|(k, v)| holds_comments(k) || holds_comments(v)),
+        Yaml::Anchor(_, ref node)
+        | Yaml::Tagged(_, ref node)
+        | Yaml::Meta(Meta::Integer(_, ref node))
+        | Yaml::Meta(Meta::String(_, ref node))
+        | Yaml::Meta(Meta::Collection(_, ref node)) => holds_comments(node),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::LoaderOptions;
     use crate::YamlLoader;
     use pretty_assertions::assert_eq;
     use std::env;
@@ -465,6 +876,92 @@ a:
         assert_roundtrip(input);
     }
 
+    #[test]
+    fn test_anchors_and_aliases() {
+        let input = r#"--- &root
+defaults: &defaults
+  image: rust
+  script:
+    - &build cargo build
+    - cargo test
+empty: &empty []
+jobs:
+  - &first
+    base: *defaults
+    name: *build
+  - *first
+&key key: *empty
+*defaults : value"#;
+
+        let options = LoaderOptions::new().anchors(true);
+        let docs = YamlLoader::load_with_options(input, options).unwrap();
+        let mut output = String::new();
+        YamlEmitter::new(&mut output).dump(&docs[0]).unwrap();
+
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let input = r#"---
+defaults: &defaults
+  image: rust
+job:
+  <<: *defaults
+  "<<": not merged"#;
+
+        let options = LoaderOptions::new().anchors(true).merge_keys(true);
+        let docs = YamlLoader::load_with_options(input, options).unwrap();
+        assert_eq!(yaml_dump(&docs[0]), input);
+
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.collection_format(CollectionFormat::Flow);
+        emitter.dump(&docs[0]["job"]).unwrap();
+        assert_eq!(output, "---\n{<<: *defaults, \"<<\": not merged}");
+
+        // without merge keys, `<<` is a string key, kept plain when merging
+        // aliases
+        let input = r#"---
+defaults: &defaults
+  image: rust
+job:
+  <<: *defaults
+other:
+  <<:
+    - *defaults
+string:
+  "<<": not merged"#;
+        let docs =
+            YamlLoader::load_with_options(input, LoaderOptions::new().anchors(true)).unwrap();
+        assert_eq!(yaml_dump(&docs[0]), input);
+    }
+
+    #[test]
+    fn test_tags() {
+        let input = r#"--- !template
+bucket: !Ref MyBucket
+arn: !GetAtt
+  - MyBucket
+  - Arn
+config: !include
+  path: a.yaml
+empty: !!set {}
+anchored: &x !env "12"
+sequence:
+  - !Ref a
+  - !!omap
+    - a: 1
+!key key: value"#;
+
+        let options = LoaderOptions::new().tags(true).anchors(true);
+        let docs = YamlLoader::load_with_options(input, options).unwrap();
+        let mut output = String::new();
+        YamlEmitter::new(&mut output).dump(&docs[0]).unwrap();
+
+        assert_eq!(input, output);
+    }
+
     fn yaml_string(s: &str) -> Yaml {
         Yaml::String(s.to_string())
     }
@@ -569,6 +1066,259 @@ e: "yes,\nquotes needed""#;
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_indent() {
+        let input = r#"---
+a:
+  - b: 1
+    c:
+      - [x, y]
+      - &s
+        - z
+? [k]
+: v
+d: !t
+  - e
+"#;
+        let docs =
+            YamlLoader::load_with_options(input, LoaderOptions::new().anchors(true).tags(true))
+                .unwrap();
+        let mut doc = docs[0].clone();
+        if let Yaml::Hash(ref mut hash) = doc {
+            let text = yaml_fmtstr("line\n  indented", StringFormat::Block);
+            hash.insert(yaml_string("f"), Yaml::Array(vec![text]));
+        }
+        let dump = |indent, indentless| {
+            let mut out = String::new();
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.indent(indent);
+            emitter.indentless_sequences(indentless);
+            emitter.dump(&doc).unwrap();
+            out
+        };
+        let load = |s: &str| {
+            YamlLoader::load_with_options(s, LoaderOptions::new().anchors(true).tags(true)).unwrap()
+        };
+
+        let expected = r#"---
+a:
+    -   b: 1
+        c:
+            -   - x
+                - y
+            - &s
+                - z
+?   - k
+: v
+d: !t
+    - e
+f:
+    - |-
+        line
+          indented"#;
+        assert_eq!(dump(4, false), expected);
+
+        let expected = r#"---
+a:
+- b: 1
+  c:
+  - - x
+    - y
+  - &s
+    - z
+? - k
+: v
+d: !t
+- e
+f:
+- |-
+  line
+    indented"#;
+        assert_eq!(dump(2, true), expected);
+
+        let expected = load(&dump(2, false));
+        for &indent in &[2, 3, 9] {
+            for &indentless in &[false, true] {
+                let output = dump(indent, indentless);
+                assert_eq!(load(&output), expected, "{}", output);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "indentation must be between 2 and 9, got 1")]
+    fn test_indent_out_of_range() {
+        YamlEmitter::new(&mut String::new()).indent(1);
+    }
+
+    #[test]
+    fn test_flow_collections() {
+        let options = || LoaderOptions::new().anchors(true).tags(true);
+        let input = "a: &x [1, 'b c', \"d,e\", ~, {f: g, [h]: i, *x : j}]\nk: !t {l: []}\nm:\n  - \
+                     n\n  - {}\n";
+        let docs = YamlLoader::load_with_options(input, options()).unwrap();
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.collection_format(CollectionFormat::Flow);
+        emitter.dump(&docs[0]).unwrap();
+        assert_eq!(
+            output,
+            "---\n{a: &x [1, b c, \"d,e\", ~, {f: g, ? [h]: i, *x : j}], k: !t {l: []}, m: [n, \
+             {}]}"
+        );
+        assert_eq!(
+            YamlLoader::load_with_options(&output, options()).unwrap(),
+            docs
+        );
+
+        // collections holding comments stay in block style
+        let input = "# head\na: [1, 2] # c\nb:\n  - 1 # one\n  - 2\n";
+        let docs =
+            YamlLoader::load_with_options(input, LoaderOptions::new().comments(true)).unwrap();
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.collection_format(CollectionFormat::Flow);
+        emitter.dump(&docs[0]).unwrap();
+        assert_eq!(output, "--- # head\na: [1, 2] # c\nb:\n  - 1 # one\n  - 2");
+
+        let collection = |format, node| Yaml::Meta(Meta::Collection(format, Box::new(node)));
+        let mut hash = Hash::new();
+        hash.insert(yaml_string("s"), yaml_fmtstr("x\ny", StringFormat::Block));
+        hash.insert(
+            yaml_string("t"),
+            collection(CollectionFormat::Block, Yaml::Array(vec![yaml_string("u")])),
+        );
+        hash.insert(
+            yaml_string("v"),
+            collection(CollectionFormat::Flow, Yaml::Array(vec![Yaml::Integer(80)])),
+        );
+        let doc = Yaml::Hash(hash);
+        assert_eq!(yaml_dump(&doc), "---\ns: |-\n  x\n  y\nt:\n  - u\nv: [80]");
+
+        // block scalars and collections cannot be nested in flow collections
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.collection_format(CollectionFormat::Flow);
+        emitter.dump(&doc).unwrap();
+        assert_eq!(output, "---\n{s: \"x\\ny\", t: [u], v: [80]}");
+    }
+
+    #[test]
+    fn test_auto_layout() {
+        let input = r#"server:
+  ports: [80, 443]
+  env: {a: 1, b: two}
+  hosts: [alpha.example.com, beta.example.com, gamma.example.com]
+  matrix:
+    - [1, 2, 3]
+    - [4, 5, 6]
+list: [x, y] # trailing
+"#;
+        let docs =
+            YamlLoader::load_with_options(input, LoaderOptions::new().comments(true)).unwrap();
+        let mut doc = docs[0].clone();
+        if let Yaml::Hash(ref mut hash) = doc {
+            let text = yaml_fmtstr("not in flow", StringFormat::Block);
+            hash.insert(yaml_string("block"), Yaml::Array(vec![text]));
+        }
+        let dump = |width| {
+            let mut output = String::new();
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.collection_format(CollectionFormat::Auto);
+            emitter.line_width(width);
+            emitter.dump(&doc).unwrap();
+            output
+        };
+
+        let expected = r#"---
+server:
+  ports: [80, 443]
+  env: {a: 1, b: two}
+  hosts: [alpha.example.com, beta.example.com, gamma.example.com]
+  matrix:
+    - [1, 2, 3]
+    - [4, 5, 6]
+list: [x, y] # trailing
+block:
+  - |-
+    not in flow"#;
+        assert_eq!(dump(80), expected);
+
+        let expected = r#"---
+server:
+  ports:
+    - 80
+    - 443
+  env:
+    a: 1
+    b: two
+  hosts:
+    - alpha.example.com
+    - beta.example.com
+    - gamma.example.com
+  matrix:
+    - [1, 2, 3]
+    - [4, 5, 6]
+list: [x, y] # trailing
+block:
+  - |-
+    not in flow"#;
+        assert_eq!(dump(17), expected);
+
+        // `  ports: [80, 443]` is 18 columns wide
+        assert!(dump(18).contains("ports: [80, 443]\n"));
+        for &width in &[10, 18, 40, 80] {
+            let output = dump(width);
+            // trailing comments are not part of the collections
+            let flow = output.lines().map(|line| line.split(" #").next().unwrap());
+            for line in flow.filter(|line| line.ends_with(']') || line.ends_with('}')) {
+                assert!(line.len() <= width, "{}", output);
+            }
+            let load = |s: &str| YamlLoader::load_with_options(s, LoaderOptions::new()).unwrap();
+            assert_eq!(load(&output), load(&dump(80)));
+        }
+    }
+
+    #[test]
+    fn test_io_writer() {
+        let docs = YamlLoader::load_from_str("a: [1, \"é\"]\n---\nb: c\n").unwrap();
+        let mut out = Vec::new();
+        {
+            let mut emitter = YamlEmitter::from_io_writer(&mut out);
+            for doc in &docs {
+                emitter.dump(doc).unwrap();
+            }
+        }
+        let expected: String = docs.iter().map(yaml_dump).collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        // a stream accepting a few bytes only
+        struct Full(usize);
+        impl io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                match self.0 {
+                    0 => Err(io::Error::new(io::ErrorKind::WriteZero, "disk full")),
+                    n => {
+                        self.0 = n.saturating_sub(buf.len());
+                        Ok(buf.len().min(n))
+                    }
+                }
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let long = Yaml::Array(vec![yaml_string(&"x".repeat(10_000))]);
+        for doc in &[&docs[0], &long] {
+            let mut full = Full(4);
+            match YamlEmitter::from_io_writer(&mut full).dump(doc) {
+                Err(EmitError::Io(e)) => assert_eq!(e.to_string(), "disk full"),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+    }
+
     fixture_test!(test_emit_simple, "emitter/simple");
 
     fixture_test!(test_emit_complex, "emitter/complex");
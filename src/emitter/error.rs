@@ -3,17 +3,25 @@ use std::fmt::Display;
 use std::fmt::Error as FmtError;
 use std::fmt::Formatter;
 use std::fmt::Result;
+use std::io;
+use std::sync::Arc;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum EmitError {
     FmtError(FmtError),
+    /// Writing to the `io::Write` of the emitter failed. The error is shared
+    /// so that `EmitError` can be cloned.
+    Io(Arc<io::Error>),
     BadHashmapKey,
     IntFmtWidth,
 }
 
 impl Error for EmitError {
-    fn cause(&self) -> Option<&dyn Error> {
-        None
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EmitError::Io(ref err) => Some(&**err),
+            _ => None,
+        }
     }
 }
 
@@ -21,6 +29,7 @@ impl Display for EmitError {
     fn fmt(&self, formatter: &mut Formatter) -> Result {
         match *self {
             EmitError::FmtError(ref err) => Display::fmt(err, formatter),
+            EmitError::Io(ref err) => Display::fmt(err, formatter),
             EmitError::BadHashmapKey => formatter.write_str("bad hashmap key"),
             EmitError::IntFmtWidth => formatter.write_str("bad integer format width"),
         }
@@ -32,3 +41,9 @@ impl From<FmtError> for EmitError {
         EmitError::FmtError(f)
     }
 }
+
+impl From<io::Error> for EmitError {
+    fn from(e: io::Error) -> Self {
+        EmitError::Io(Arc::new(e))
+    }
+}
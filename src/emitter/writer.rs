@@ -0,0 +1,119 @@
+use super::EmitError;
+use super::EmitResult;
+use std::fmt;
+use std::io;
+
+const BUFFER_SIZE: usize = 8192;
+
+/// Destination of the emitter, keeping track of the column written at.
+pub struct Writer<'a> {
+    output: Output<'a>,
+    // in chars, from the last line break
+    column: usize,
+    // set while measuring, see `measure`
+    limit: Option<usize>,
+}
+
+// Not a `BufWriter`, whose destructor would keep the stream borrowed until
+// the emitter is dropped.
+enum Output<'a> {
+    Fmt(&'a mut dyn fmt::Write),
+    Io {
+        writer: &'a mut dyn io::Write,
+        buffer: Vec<u8>,
+        // `fmt::Error` carries no information, so the error is kept here
+        error: Option<io::Error>,
+    },
+}
+
+impl<'a> Writer<'a> {
+    pub fn fmt(writer: &'a mut dyn fmt::Write) -> Writer<'a> {
+        Writer::new(Output::Fmt(writer))
+    }
+
+    pub fn io(writer: &'a mut dyn io::Write) -> Writer<'a> {
+        Writer::new(Output::Io {
+            writer,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            error: None,
+        })
+    }
+
+    fn new(output: Output<'a>) -> Writer<'a> {
+        Writer {
+            output,
+            column: 0,
+            limit: None,
+        }
+    }
+
+    /// Write nothing until `stop_measuring`, failing instead at a line break
+    /// or past the column `limit`. Returns the column to restore then.
+    pub fn measure(&mut self, limit: usize) -> usize {
+        self.limit = Some(limit);
+        self.column
+    }
+
+    pub fn stop_measuring(&mut self, column: usize) {
+        self.limit = None;
+        self.column = column;
+    }
+
+    /// Turn the result of emitting a document into the error that caused
+    /// it, and flush the document when it is complete.
+    pub fn finish(&mut self, result: EmitResult) -> EmitResult {
+        match self.output {
+            Output::Fmt(_) => result,
+            Output::Io {
+                ref mut writer,
+                ref mut buffer,
+                ref mut error,
+            } => {
+                let result = match error.take() {
+                    Some(e) => Err(EmitError::from(e)),
+                    None => result.and_then(|()| writer.write_all(buffer).map_err(EmitError::from)),
+                };
+                buffer.clear();
+                result?;
+                writer.flush()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Write for Writer<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match s.rfind('\n') {
+            Some(_) if self.limit.is_some() => return Err(fmt::Error),
+            Some(i) => self.column = s[i + 1..].chars().count(),
+            None => self.column += s.chars().count(),
+        }
+        if let Some(limit) = self.limit {
+            return match self.column {
+                column if column > limit => Err(fmt::Error),
+                _ => Ok(()),
+            };
+        }
+
+        match self.output {
+            Output::Fmt(ref mut writer) => writer.write_str(s),
+            Output::Io {
+                ref mut writer,
+                ref mut buffer,
+                ref mut error,
+            } => {
+                buffer.extend_from_slice(s.as_bytes());
+                if buffer.len() < BUFFER_SIZE {
+                    return Ok(());
+                }
+                let result = writer.write_all(buffer);
+                buffer.clear();
+                result.map_err(|e| {
+                    *error = Some(e);
+                    fmt::Error
+                })
+            }
+        }
+    }
+}
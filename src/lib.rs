@@ -54,7 +54,13 @@ pub mod yaml;
 pub use crate::emitter::EmitError;
 pub use crate::emitter::YamlEmitter;
 pub use crate::parser::Event;
+pub use crate::scanner::ErrorKind;
 pub use crate::scanner::ScanError;
+pub use crate::yaml::DuplicateKeys;
+pub use crate::yaml::LoaderOptions;
+pub use crate::yaml::MarkedYaml;
+pub use crate::yaml::MarkedYamlLoader;
+pub use crate::yaml::Schema;
 pub use crate::yaml::Yaml;
 pub use crate::yaml::YamlLoader;
 
@@ -118,4 +124,32 @@ key1:a2
         assert!(YamlLoader::load_from_str(s).is_err());
         assert!(try_fail(s).is_err());
     }
+
+    #[test]
+    fn test_error_kinds() {
+        let kinds = [
+            ("a: [1, 2", ErrorKind::UnexpectedEof),
+            ("a: 'b", ErrorKind::UnexpectedEof),
+            ("a:\n  b: 1\n c: 2\n", ErrorKind::BadIndentation),
+            ("a:\n  b\n\tc\n", ErrorKind::TabIndentation),
+            ("a: b: c", ErrorKind::Syntax),
+            ("a: \"\\q\"", ErrorKind::InvalidEscape),
+            ("%YAML 2.0\n--- a", ErrorKind::InvalidDirective),
+            ("a: !e!b c", ErrorKind::InvalidTag),
+            ("a: *b", ErrorKind::UnknownAnchor),
+            ("a: 1\na: 2", ErrorKind::DuplicateKey),
+        ];
+        let options = LoaderOptions::new().duplicate_keys(DuplicateKeys::Error);
+        for &(s, kind) in &kinds {
+            let err = YamlLoader::load_with_options(s, options.clone()).unwrap_err();
+            assert_eq!(err.kind(), kind, "{}: {}", s, err);
+        }
+        let options = LoaderOptions::new().max_depth(1);
+        let err = YamlLoader::load_with_options("[[a]]", options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        assert_eq!(
+            err.info(),
+            "while parsing a node, exceeded the maximum nesting depth of 1"
+        );
+    }
 }
@@ -1,68 +1,237 @@
 use self::event::empty_scalar;
 use self::event::empty_scalar_with_anchor;
+pub use self::event::AnchorID;
 pub use self::event::Event;
+pub use self::event::Tag;
 use self::state::State;
+use crate::scanner::Decoder;
+use crate::scanner::ErrorKind;
 use crate::scanner::Marker;
 use crate::scanner::ScanError;
 use crate::scanner::Scanner;
+use crate::scanner::Span;
 use crate::scanner::Token;
 use crate::scanner::TokenType;
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io;
 
 mod event;
 mod state;
 
-pub type ParseResult = Result<(Event, Marker), ScanError>;
-
+pub type ParseResult = Result<(Event, Span), ScanError>;
+
+/// Nesting depth of collections allowed by default, see `Parser::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// A YAML parser, pushing events to a receiver with `load`, or yielding them
+/// one by one as an `Iterator`.
+///
+/// When iterated, the parser yields every event up to and including
+/// `StreamEnd`, or up to the first error. Comments are yielded too when
+/// parsing with comments. The receiver of a parser built with `new` still
+/// gets `on_anchor` and `on_warning`, build it with `events` if those are not
+/// needed:
+///
+/// ```
+/// use yaml_rust::parser::{Event, Parser};
+///
+/// let mut parser = Parser::events("a: [1, 2]\nb: 3\n".chars(), false);
+/// let scalars = parser
+///     .by_ref()
+///     .map(|ev| ev.unwrap().0)
+///     .filter(|ev| matches!(ev, Event::Scalar(..)))
+///     .take(2)
+///     .count();
+/// assert_eq!(scalars, 2);
+/// ```
 #[derive(Debug)]
 pub struct Parser<'re, T, R> {
-    recv: &'re mut R,
+    recv: Option<&'re mut R>,
     scanner: Scanner<T>,
     states: Vec<State>,
     state: State,
     token: Option<Token>,
-    current: Option<(Event, Marker)>,
+    current: Option<(Event, Span)>,
+    // comments found while parsing `current`, which they come before
+    comments: VecDeque<(Event, Span)>,
+    // set once the iterator has yielded `StreamEnd` or an error
+    finished: bool,
+    // collections holding the next event: what they are, and where they
+    // start
+    collections: Vec<(&'static str, Marker)>,
+    max_depth: usize,
+    // end of the last token with content, where implicit ends are reported
+    last_end: Marker,
     anchors: HashMap<String, usize>,
     anchor_id: usize,
+    // tag handle -> prefix, for the current document
+    tag_directives: HashMap<String, String>,
 }
 
 pub trait EventReceiver {
     fn on_event(&mut self, ev: Event, mark: Marker);
+
+    /// Called when the parser registers a new anchor, before the event of the
+    /// anchored node is received. Anchors are referred to by `id` in events.
+    fn on_anchor(&mut self, _id: AnchorID, _name: &str) {}
+
+    /// Called when the parser finds something suspicious but keeps parsing,
+    /// e.g. a `%YAML` directive with a minor version it does not know.
+    fn on_warning(&mut self, _mark: Marker, _message: &str) {}
+}
+
+/// Like `EventReceiver`, but receiving the full span of each event instead
+/// of its start only. Every `EventReceiver` is a `SpannedEventReceiver`.
+///
+/// Scalars, aliases and flow collection bounds span their source text,
+/// properties excluded. Block collection starts and empty scalars take no
+/// room, and block collection ends are reported where their last entry
+/// ends.
+pub trait SpannedEventReceiver {
+    fn on_event(&mut self, ev: Event, span: Span);
+
+    /// See `EventReceiver::on_anchor`.
+    fn on_anchor(&mut self, _id: AnchorID, _name: &str) {}
+
+    /// See `EventReceiver::on_warning`.
+    fn on_warning(&mut self, _mark: Marker, _message: &str) {}
+}
+
+/// Like `SpannedEventReceiver`, but able to stop the parser: the first error
+/// returned by `on_event` aborts `Parser::load`, which returns it. Errors
+/// should be built with the span of the offending event, e.g.
+/// `ScanError::new(span.start, "unexpected tag")`. Every
+/// `SpannedEventReceiver` is a `TryEventReceiver` that never fails.
+pub trait TryEventReceiver {
+    fn on_event(&mut self, ev: Event, span: Span) -> Result<(), ScanError>;
+
+    /// See `EventReceiver::on_anchor`.
+    fn on_anchor(&mut self, _id: AnchorID, _name: &str) {}
+
+    /// See `EventReceiver::on_warning`.
+    fn on_warning(&mut self, _mark: Marker, _message: &str) {}
+}
+
+impl<R: SpannedEventReceiver> TryEventReceiver for R {
+    fn on_event(&mut self, ev: Event, span: Span) -> Result<(), ScanError> {
+        SpannedEventReceiver::on_event(self, ev, span);
+        Ok(())
+    }
+
+    fn on_anchor(&mut self, id: AnchorID, name: &str) {
+        SpannedEventReceiver::on_anchor(self, id, name)
+    }
+
+    fn on_warning(&mut self, mark: Marker, message: &str) {
+        SpannedEventReceiver::on_warning(self, mark, message)
+    }
 }
 
-impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
+impl<R: EventReceiver> SpannedEventReceiver for R {
+    fn on_event(&mut self, ev: Event, span: Span) {
+        EventReceiver::on_event(self, ev, span.start)
+    }
+
+    fn on_anchor(&mut self, id: AnchorID, name: &str) {
+        EventReceiver::on_anchor(self, id, name)
+    }
+
+    fn on_warning(&mut self, mark: Marker, message: &str) {
+        EventReceiver::on_warning(self, mark, message)
+    }
+}
+
+/// Receiver of a `Parser` built with `Parser::events`, which has none. It
+/// cannot be built.
+#[derive(Debug)]
+pub enum NoReceiver {}
+
+impl TryEventReceiver for NoReceiver {
+    fn on_event(&mut self, _ev: Event, _span: Span) -> Result<(), ScanError> {
+        match *self {}
+    }
+}
+
+impl<T: Iterator<Item = char>> Parser<'static, T, NoReceiver> {
+    /// Parser of `src` without a receiver, to iterate over its events.
+    pub fn events(src: T, with_comments: bool) -> Parser<'static, T, NoReceiver> {
+        Parser::from_scanner(Scanner::new(src, with_comments), None)
+    }
+}
+
+impl<'re, T: Iterator<Item = char>, R: TryEventReceiver> Parser<'re, T, R> {
     pub fn new(src: T, recv: &'re mut R, with_comments: bool) -> Parser<T, R> {
+        Parser::from_scanner(Scanner::new(src, with_comments), Some(recv))
+    }
+
+    fn from_scanner(scanner: Scanner<T>, recv: Option<&'re mut R>) -> Parser<'re, T, R> {
         Parser {
             recv,
-            scanner: Scanner::new(src, with_comments),
+            scanner,
             states: Vec::new(),
             state: State::StreamStart,
             token: None,
             current: None,
+            comments: VecDeque::new(),
+            finished: false,
+            collections: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            last_end: Marker::new(0, 1, 0),
 
             anchors: HashMap::new(),
             // valid anchor_id starts from 1
             anchor_id: 1,
+            tag_directives: default_tag_directives(),
         }
     }
 
-    fn next(&mut self) -> ParseResult {
-        match self.current {
+    /// Fail to parse collections nested more than `max_depth` levels deep,
+    /// whether in block or flow style. Loading a document recurses on its
+    /// collections, so this bounds the stack used. Defaults to
+    /// `DEFAULT_MAX_DEPTH`.
+    pub fn max_depth(mut self, max_depth: usize) -> Parser<'re, T, R> {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub(crate) fn receiver(&mut self) -> &mut R {
+        self.recv.as_mut().expect("parser without a receiver")
+    }
+
+    /// Peek at the event `next` returns, without consuming it.
+    pub fn peek(&mut self) -> Result<&(Event, Span), ScanError> {
+        if self.comments.is_empty() && self.current.is_none() {
+            self.current = Some(self.parse()?);
+        }
+        Ok(self.comments.front().or(self.current.as_ref()).unwrap())
+    }
+
+    // next event other than a comment, for `load`
+    fn next_node_event(&mut self) -> ParseResult {
+        let event = match self.current.take() {
             None => self.parse(),
-            Some(_) => Ok(self.current.take().unwrap()),
+            Some(event) => Ok(event),
+        };
+        while let Some((comment, span)) = self.comments.pop_front() {
+            self.emit(comment, span)?;
         }
+        event
     }
 
-    fn emit(&mut self, event: Event, mark: Marker) {
-        self.recv.on_event(event, mark);
+    fn emit(&mut self, event: Event, span: Span) -> Result<(), ScanError> {
+        match self.recv {
+            Some(ref mut recv) => recv.on_event(event, span),
+            None => Ok(()),
+        }
     }
 
     fn peek_token(&mut self) -> Result<&Token, ScanError> {
         if self.token.is_none() {
             let mut next = self.scan_next_token()?;
-            while let Token(mark, TokenType::Comment(comment, inline)) = next {
-                let event = Event::Comment(comment, inline);
-                self.emit(event, mark);
+            while let Token(span, TokenType::Comment(comment, inline)) = next {
+                self.comments
+                    .push_back((Event::Comment(comment, inline), span));
                 next = self.scan_next_token()?;
             }
             self.token = Some(next)
@@ -74,7 +243,8 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
         let token = self.scanner.next();
         match token {
             None => match self.scanner.get_error() {
-                None => Err(ScanError::new(self.scanner.get_mark(), "unexpected eof")),
+                None => Err(ScanError::new(self.scanner.get_mark(), "unexpected eof")
+                    .with_kind(ErrorKind::UnexpectedEof)),
                 Some(e) => Err(e),
             },
             Some(tok) => Ok(tok),
@@ -82,13 +252,22 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
     }
 
     fn fetch_token(&mut self) -> Token {
-        self.token
+        let token = self
+            .token
             .take()
-            .expect("fetch_token needs to be preceded by peek_token")
+            .expect("fetch_token needs to be preceded by peek_token");
+        if !token.0.is_empty() {
+            self.last_end = token.0.end;
+        }
+        token
     }
 
     fn skip(&mut self) {
-        self.token = None;
+        if let Some(Token(span, _)) = self.token.take() {
+            if !span.is_empty() {
+                self.last_end = span.end;
+            }
+        }
         //self.peek_token();
     }
 
@@ -101,33 +280,69 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
     }
 
     fn parse(&mut self) -> ParseResult {
-        match self.state {
-            State::End => Ok((Event::StreamEnd, self.scanner.get_mark())),
-            _ => Ok(self.state_machine()?),
+        let (ev, span) = match self.state {
+            State::End => return Ok((Event::StreamEnd, Span::empty(self.scanner.get_mark()))),
+            _ => self.state_machine().map_err(|e| self.in_collection(e))?,
+        };
+        match ev {
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                let collection = match self.state {
+                    State::BlockSequenceFirstEntry | State::IndentlessSequenceEntry => {
+                        "block sequence"
+                    }
+                    State::BlockMappingFirstKey => "block mapping",
+                    State::FlowSequenceFirstEntry => "flow sequence",
+                    _ => "flow mapping",
+                };
+                self.collections.push((collection, span.start));
+                if self.collections.len() > self.max_depth {
+                    return Err(ScanError::new(
+                        span.start,
+                        &format!(
+                            "while parsing a node, exceeded the maximum nesting depth of {}",
+                            self.max_depth
+                        ),
+                    )
+                    .with_kind(ErrorKind::LimitExceeded));
+                }
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.collections.pop();
+            }
+            _ => {}
+        }
+        Ok((ev, span))
+    }
+
+    // error found in a collection, pointing at where the collection starts
+    fn in_collection(&self, e: ScanError) -> ScanError {
+        match self.collections.last() {
+            Some(&(collection, mark)) if e.context_marker().is_none() => {
+                e.with_context(mark, &format!("while parsing a {} started", collection))
+            }
+            _ => e,
         }
     }
 
     pub fn load(&mut self, multi: bool) -> Result<(), ScanError> {
         if !self.scanner.is_stream_started() {
-            let (ev, mark) = self.next()?;
+            let (ev, span) = self.next_node_event()?;
             assert_eq!(ev, Event::StreamStart);
-            self.emit(ev, mark);
+            self.emit(ev, span)?;
         }
 
         if self.scanner.is_stream_finished() {
             // XXX has parsed?
-            self.emit(Event::StreamEnd, self.scanner.get_mark());
+            self.emit(Event::StreamEnd, Span::empty(self.scanner.get_mark()))?;
             return Ok(());
         }
         loop {
-            let (ev, mark) = self.next()?;
+            let (ev, span) = self.next_node_event()?;
             if ev == Event::StreamEnd {
-                self.emit(ev, mark);
+                self.emit(ev, span)?;
                 return Ok(());
             }
-            // clear anchors before a new document
-            self.anchors.clear();
-            self.load_document(ev, mark)?;
+            self.load_document(ev, span)?;
             if !multi {
                 break;
             }
@@ -135,33 +350,30 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
         Ok(())
     }
 
-    fn load_document(&mut self, ev: Event, mark: Marker) -> Result<(), ScanError> {
-        assert_eq!(ev, Event::DocumentStart);
-        self.emit(ev, mark);
+    fn load_document(&mut self, ev: Event, span: Span) -> Result<(), ScanError> {
+        assert!(matches!(ev, Event::DocumentStart(_)));
+        self.emit(ev, span)?;
 
-        let (ev, mark) = self.next()?;
-        self.load_node(ev, mark)?;
+        let (ev, span) = self.next_node_event()?;
+        self.load_node(ev, span)?;
 
         // DOCUMENT-END is expected.
-        let (ev, mark) = self.next()?;
+        let (ev, span) = self.next_node_event()?;
         assert_eq!(ev, Event::DocumentEnd);
-        self.emit(ev, mark);
+        self.emit(ev, span)?;
 
         Ok(())
     }
 
-    fn load_node(&mut self, ev: Event, mark: Marker) -> Result<(), ScanError> {
+    fn load_node(&mut self, ev: Event, span: Span) -> Result<(), ScanError> {
         match ev {
-            Event::Alias(..) | Event::Scalar(..) => {
-                self.emit(ev, mark);
-                Ok(())
-            }
-            Event::SequenceStart(_) => {
-                self.emit(ev, mark);
+            Event::Alias(..) | Event::Scalar(..) => self.emit(ev, span),
+            Event::SequenceStart(..) => {
+                self.emit(ev, span)?;
                 self.load_sequence()
             }
-            Event::MappingStart(_) => {
-                self.emit(ev, mark);
+            Event::MappingStart(..) => {
+                self.emit(ev, span)?;
                 self.load_mapping()
             }
             _ => {
@@ -172,29 +384,29 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
     }
 
     fn load_mapping(&mut self) -> Result<(), ScanError> {
-        let (mut key_ev, mut key_mark) = self.next()?;
+        let (mut key_ev, mut key_span) = self.next_node_event()?;
         while key_ev != Event::MappingEnd {
             // key
-            self.load_node(key_ev, key_mark)?;
+            self.load_node(key_ev, key_span)?;
 
             // value
-            let (val_ev, val_mark) = self.next()?;
-            self.load_node(val_ev, val_mark)?;
+            let (val_ev, val_span) = self.next_node_event()?;
+            self.load_node(val_ev, val_span)?;
 
             // next event
-            (key_ev, key_mark) = self.next()?;
+            (key_ev, key_span) = self.next_node_event()?;
         }
-        self.emit(key_ev, key_mark);
+        self.emit(key_ev, key_span)?;
         Ok(())
     }
 
     fn load_sequence(&mut self) -> Result<(), ScanError> {
-        let (mut ev, mut mark) = self.next()?;
+        let (mut ev, mut span) = self.next_node_event()?;
         while ev != Event::SequenceEnd {
-            self.load_node(ev, mark)?;
-            (ev, mark) = self.next()?;
+            self.load_node(ev, span)?;
+            (ev, span) = self.next_node_event()?;
         }
-        self.emit(ev, mark);
+        self.emit(ev, span)?;
         Ok(())
     }
 
@@ -240,12 +452,17 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
 
     fn stream_start(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token(mark, TokenType::StreamStart(_)) => {
+            Token(span, TokenType::StreamStart(_)) => {
                 self.state = State::ImplicitDocumentStart;
                 self.skip();
-                Ok((Event::StreamStart, mark))
+                Ok((Event::StreamStart, span))
             }
-            Token(mark, _) => Err(ScanError::new(mark, "did not find expected <stream-start>")),
+            Token(span, ref token) => Err(unexpected_token(
+                token,
+                ErrorKind::Syntax,
+                span,
+                "did not find expected <stream-start>",
+            )),
         }
     }
 
@@ -255,12 +472,14 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                 self.skip();
             }
         }
+        // anchors are scoped to their document
+        self.anchors.clear();
 
         match *self.peek_token()? {
-            Token(mark, TokenType::StreamEnd) => {
+            Token(span, TokenType::StreamEnd) => {
                 self.state = State::End;
                 self.skip();
-                Ok((Event::StreamEnd, mark))
+                Ok((Event::StreamEnd, span))
             }
             Token(_, TokenType::VersionDirective(..))
             | Token(_, TokenType::TagDirective(..))
@@ -268,11 +487,11 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                 // explicit document
                 self._explicit_document_start()
             }
-            Token(mark, _) if implicit => {
-                self.parser_process_directives()?;
+            Token(span, _) if implicit => {
+                let version = self.parser_process_directives()?;
                 self.push_state(State::DocumentEnd);
                 self.state = State::BlockNode;
-                Ok((Event::DocumentStart, mark))
+                Ok((Event::DocumentStart(version), span))
             }
             _ => {
                 // explicit document
@@ -281,38 +500,76 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
         }
     }
 
-    fn parser_process_directives(&mut self) -> Result<(), ScanError> {
+    fn parser_process_directives(&mut self) -> Result<Option<(u32, u32)>, ScanError> {
+        let mut version = None;
+        let mut tag_directives = HashMap::new();
         loop {
-            match self.peek_token()?.1 {
-                TokenType::VersionDirective(_, _) => {
-                    // XXX parsing with warning according to spec
-                    //if major != 1 || minor > 2 {
-                    //    return Err(ScanError::new(tok.0,
-                    //        "found incompatible YAML document"));
-                    //}
+            match *self.peek_token()? {
+                Token(span, TokenType::VersionDirective(major, minor)) => {
+                    if version.is_some() {
+                        return Err(ScanError::new(
+                            span.start,
+                            "while parsing a document, found duplicate %YAML directive",
+                        )
+                        .with_kind(ErrorKind::InvalidDirective));
+                    }
+                    if major != 1 {
+                        return Err(ScanError::new(
+                            span.start,
+                            "while parsing a document, found incompatible YAML document",
+                        )
+                        .with_kind(ErrorKind::InvalidDirective));
+                    }
+                    if minor > 2 {
+                        // parsing with warning according to spec
+                        if let Some(ref mut recv) = self.recv {
+                            recv.on_warning(
+                                span.start,
+                                &format!(
+                                    "found YAML {}.{} document, parsing it as YAML 1.2",
+                                    major, minor
+                                ),
+                            );
+                        }
+                    }
+                    version = Some((major, minor));
                 }
-                TokenType::TagDirective(..) => {
-                    // TODO add tag directive
+                Token(span, TokenType::TagDirective(ref handle, ref prefix)) => {
+                    // unknown directives are scanned as empty tag directives
+                    if !handle.is_empty() {
+                        if tag_directives.contains_key(handle) {
+                            return Err(ScanError::new(
+                                span.start,
+                                "while parsing a document, found duplicate %TAG directive",
+                            )
+                            .with_kind(ErrorKind::InvalidDirective));
+                        }
+                        tag_directives.insert(handle.clone(), prefix.clone());
+                    }
                 }
                 _ => break,
             }
             self.skip();
         }
-        // TODO tag directive
-        Ok(())
+        // directives only apply to the document that follows them
+        self.tag_directives = default_tag_directives();
+        self.tag_directives.extend(tag_directives);
+        Ok(version)
     }
 
     fn _explicit_document_start(&mut self) -> ParseResult {
-        self.parser_process_directives()?;
+        let version = self.parser_process_directives()?;
         match *self.peek_token()? {
-            Token(mark, TokenType::DocumentStart) => {
+            Token(span, TokenType::DocumentStart) => {
                 self.push_state(State::DocumentEnd);
                 self.state = State::DocumentContent;
                 self.skip();
-                Ok((Event::DocumentStart, mark))
+                Ok((Event::DocumentStart(version), span))
             }
-            Token(mark, _) => Err(ScanError::new(
-                mark,
+            Token(span, ref token) => Err(unexpected_token(
+                token,
+                ErrorKind::Syntax,
+                span,
                 "did not find expected <document start>",
             )),
         }
@@ -320,14 +577,14 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
 
     fn document_content(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token(mark, TokenType::VersionDirective(..))
-            | Token(mark, TokenType::TagDirective(..))
-            | Token(mark, TokenType::DocumentStart)
-            | Token(mark, TokenType::DocumentEnd)
-            | Token(mark, TokenType::StreamEnd) => {
+            Token(span, TokenType::VersionDirective(..))
+            | Token(span, TokenType::TagDirective(..))
+            | Token(span, TokenType::DocumentStart)
+            | Token(span, TokenType::DocumentEnd)
+            | Token(span, TokenType::StreamEnd) => {
                 self.pop_state();
                 // empty scalar
-                Ok((empty_scalar(), mark))
+                Ok((empty_scalar(), Span::empty(span.start)))
             }
             _ => self.parse_node(true, false),
         }
@@ -335,18 +592,18 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
 
     fn document_end(&mut self) -> ParseResult {
         let mut _implicit = true;
-        let marker: Marker = match *self.peek_token()? {
-            Token(mark, TokenType::DocumentEnd) => {
+        let span = match *self.peek_token()? {
+            Token(span, TokenType::DocumentEnd) => {
                 self.skip();
                 _implicit = false;
-                mark
+                span
             }
-            Token(mark, _) => mark,
+            Token(span, _) => Span::empty(span.start),
         };
 
         // TODO tag handling
         self.state = State::DocumentStart;
-        Ok((Event::DocumentEnd, marker))
+        Ok((Event::DocumentEnd, span))
     }
 
     fn register_anchor(&mut self, name: String, _: &Marker) -> Result<usize, ScanError> {
@@ -357,96 +614,127 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
         // }
         let new_id = self.anchor_id;
         self.anchor_id += 1;
+        if let Some(ref mut recv) = self.recv {
+            recv.on_anchor(new_id, &name);
+        }
         self.anchors.insert(name, new_id);
         Ok(new_id)
     }
 
+    /// Fetch the next token, which must be a tag, and resolve its handle.
+    fn fetch_tag(&mut self) -> Result<Tag, ScanError> {
+        if let Token(span, TokenType::Tag(handle, suffix)) = self.fetch_token() {
+            let uri = if handle.is_empty() {
+                // verbatim or non-specific tag
+                suffix.clone()
+            } else {
+                match self.tag_directives.get(&handle) {
+                    Some(prefix) => format!("{}{}", prefix, suffix),
+                    None => {
+                        return Err(ScanError::new(
+                            span.start,
+                            "while parsing a node, found undefined tag handle",
+                        )
+                        .with_span(span)
+                        .with_kind(ErrorKind::InvalidTag))
+                    }
+                }
+            };
+            Ok(Tag {
+                handle,
+                suffix,
+                uri,
+            })
+        } else {
+            unreachable!()
+        }
+    }
+
     fn parse_node(&mut self, block: bool, indentless_sequence: bool) -> ParseResult {
         let mut anchor_id = 0;
         let mut tag = None;
         match self.peek_token()? {
             Token(_, TokenType::Alias(_)) => {
                 self.pop_state();
-                if let Token(mark, TokenType::Alias(name)) = self.fetch_token() {
+                if let Token(span, TokenType::Alias(name)) = self.fetch_token() {
                     match self.anchors.get(&name) {
                         None => {
                             return Err(ScanError::new(
-                                mark,
+                                span.start,
                                 "while parsing node, found unknown anchor",
-                            ))
+                            )
+                            .with_span(span)
+                            .with_kind(ErrorKind::UnknownAnchor))
                         }
-                        Some(id) => return Ok((Event::Alias(*id), mark)),
+                        Some(id) => return Ok((Event::Alias(*id), span)),
                     }
                 } else {
                     unreachable!()
                 }
             }
             Token(_, TokenType::Anchor(_)) => {
-                if let Token(mark, TokenType::Anchor(name)) = self.fetch_token() {
-                    anchor_id = self.register_anchor(name, &mark)?;
+                if let Token(span, TokenType::Anchor(name)) = self.fetch_token() {
+                    anchor_id = self.register_anchor(name, &span.start)?;
                     if let TokenType::Tag(..) = self.peek_token()?.1 {
-                        if let tg @ TokenType::Tag(..) = self.fetch_token().1 {
-                            tag = Some(tg);
-                        } else {
-                            unreachable!()
-                        }
+                        tag = Some(self.fetch_tag()?);
                     }
                 } else {
                     unreachable!()
                 }
             }
             Token(_, TokenType::Tag(..)) => {
-                if let tg @ TokenType::Tag(..) = self.fetch_token().1 {
-                    tag = Some(tg);
-                    if let TokenType::Anchor(_) = self.peek_token()?.1 {
-                        if let Token(mark, TokenType::Anchor(name)) = self.fetch_token() {
-                            anchor_id = self.register_anchor(name, &mark)?;
-                        } else {
-                            unreachable!()
-                        }
+                tag = Some(self.fetch_tag()?);
+                if let TokenType::Anchor(_) = self.peek_token()?.1 {
+                    if let Token(span, TokenType::Anchor(name)) = self.fetch_token() {
+                        anchor_id = self.register_anchor(name, &span.start)?;
+                    } else {
+                        unreachable!()
                     }
-                } else {
-                    unreachable!()
                 }
             }
             _ => {}
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::BlockEntry) if indentless_sequence => {
+            Token(span, TokenType::BlockEntry) if indentless_sequence => {
                 self.state = State::IndentlessSequenceEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), span))
             }
             Token(_, TokenType::Scalar(..)) => {
                 self.pop_state();
-                if let Token(mark, TokenType::Scalar(style, v)) = self.fetch_token() {
-                    Ok((Event::Scalar(v, style, anchor_id, tag), mark))
+                if let Token(span, TokenType::Scalar(style, v)) = self.fetch_token() {
+                    Ok((Event::Scalar(v, style, anchor_id, tag), span))
                 } else {
                     unreachable!()
                 }
             }
-            Token(mark, TokenType::FlowSequenceStart) => {
+            Token(span, TokenType::FlowSequenceStart) => {
                 self.state = State::FlowSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), span))
             }
-            Token(mark, TokenType::FlowMappingStart) => {
+            Token(span, TokenType::FlowMappingStart) => {
                 self.state = State::FlowMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id), mark))
+                Ok((Event::MappingStart(anchor_id, tag), span))
             }
-            Token(mark, TokenType::BlockSequenceStart) if block => {
+            Token(span, TokenType::BlockSequenceStart) if block => {
                 self.state = State::BlockSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), span))
             }
-            Token(mark, TokenType::BlockMappingStart) if block => {
+            Token(span, TokenType::BlockMappingStart) if block => {
                 self.state = State::BlockMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id), mark))
+                Ok((Event::MappingStart(anchor_id, tag), span))
             }
             // ex 7.2, an empty scalar can follow a secondary tag
-            Token(mark, _) if tag.is_some() || anchor_id > 0 => {
+            Token(span, _) if tag.is_some() || anchor_id > 0 => {
                 self.pop_state();
-                Ok((empty_scalar_with_anchor(anchor_id, tag), mark))
+                Ok((
+                    empty_scalar_with_anchor(anchor_id, tag),
+                    Span::empty(span.start),
+                ))
             }
-            Token(mark, _) => Err(ScanError::new(
-                mark,
+            Token(span, ref token) => Err(unexpected_token(
+                token,
+                ErrorKind::Syntax,
+                span,
                 "while parsing a node, did not find expected node content",
             )),
         }
@@ -462,12 +750,12 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
             Token(_, TokenType::Key) => {
                 self.skip();
                 match *self.peek_token()? {
-                    Token(mark, TokenType::Key)
-                    | Token(mark, TokenType::Value)
-                    | Token(mark, TokenType::BlockEnd) => {
+                    Token(span, TokenType::Key)
+                    | Token(span, TokenType::Value)
+                    | Token(span, TokenType::BlockEnd) => {
                         self.state = State::BlockMappingValue;
                         // empty scalar
-                        Ok((empty_scalar(), mark))
+                        Ok((empty_scalar(), Span::empty(span.start)))
                     }
                     _ => {
                         self.push_state(State::BlockMappingValue);
@@ -476,17 +764,19 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                 }
             }
             // XXX(chenyh): libyaml failed to parse spec 1.2, ex8.18
-            Token(mark, TokenType::Value) => {
+            Token(span, TokenType::Value) => {
                 self.state = State::BlockMappingValue;
-                Ok((empty_scalar(), mark))
+                Ok((empty_scalar(), Span::empty(span.start)))
             }
-            Token(mark, TokenType::BlockEnd) => {
+            Token(_, TokenType::BlockEnd) => {
                 self.pop_state();
                 self.skip();
-                Ok((Event::MappingEnd, mark))
+                Ok((Event::MappingEnd, Span::empty(self.last_end)))
             }
-            Token(mark, _) => Err(ScanError::new(
-                mark,
+            Token(span, ref token) => Err(unexpected_token(
+                token,
+                ErrorKind::BadIndentation,
+                span,
                 "while parsing a block mapping, did not find expected key",
             )),
         }
@@ -497,12 +787,12 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
             Token(_, TokenType::Value) => {
                 self.skip();
                 match *self.peek_token()? {
-                    Token(mark, TokenType::Key)
-                    | Token(mark, TokenType::Value)
-                    | Token(mark, TokenType::BlockEnd) => {
+                    Token(span, TokenType::Key)
+                    | Token(span, TokenType::Value)
+                    | Token(span, TokenType::BlockEnd) => {
                         self.state = State::BlockMappingKey;
                         // empty scalar
-                        Ok((empty_scalar(), mark))
+                        Ok((empty_scalar(), Span::empty(span.start)))
                     }
                     _ => {
                         self.push_state(State::BlockMappingKey);
@@ -510,10 +800,10 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                     }
                 }
             }
-            Token(mark, _) => {
+            Token(span, _) => {
                 self.state = State::BlockMappingKey;
                 // empty scalar
-                Ok((empty_scalar(), mark))
+                Ok((empty_scalar(), Span::empty(span.start)))
             }
         }
     }
@@ -523,16 +813,18 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
             let _ = self.peek_token()?;
             self.skip();
         }
-        let marker: Marker = {
+        {
             match *self.peek_token()? {
-                Token(mark, TokenType::FlowMappingEnd) => mark,
-                Token(mark, _) => {
+                Token(_, TokenType::FlowMappingEnd) => (),
+                Token(..) => {
                     if !first {
                         match *self.peek_token()? {
                             Token(_, TokenType::FlowEntry) => self.skip(),
-                            Token(mark, _) => {
-                                return Err(ScanError::new(
-                                    mark,
+                            Token(span, ref token) => {
+                                return Err(unexpected_token(
+                                    token,
+                                    ErrorKind::Syntax,
+                                    span,
                                     "while parsing a flow mapping, did not find expected ',' or \
                                      '}'",
                                 ))
@@ -544,11 +836,11 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                         Token(_, TokenType::Key) => {
                             self.skip();
                             match *self.peek_token()? {
-                                Token(mark, TokenType::Value)
-                                | Token(mark, TokenType::FlowEntry)
-                                | Token(mark, TokenType::FlowMappingEnd) => {
+                                Token(span, TokenType::Value)
+                                | Token(span, TokenType::FlowEntry)
+                                | Token(span, TokenType::FlowMappingEnd) => {
                                     self.state = State::FlowMappingValue;
-                                    return Ok((empty_scalar(), mark));
+                                    return Ok((empty_scalar(), Span::empty(span.start)));
                                 }
                                 _ => {
                                     self.push_state(State::FlowMappingValue);
@@ -556,9 +848,9 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                                 }
                             }
                         }
-                        Token(marker, TokenType::Value) => {
+                        Token(span, TokenType::Value) => {
                             self.state = State::FlowMappingValue;
-                            return Ok((empty_scalar(), marker));
+                            return Ok((empty_scalar(), Span::empty(span.start)));
                         }
                         Token(_, TokenType::FlowMappingEnd) => (),
                         _ => {
@@ -566,26 +858,25 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                             return self.parse_node(false, false);
                         }
                     }
-
-                    mark
                 }
             }
-        };
+        }
 
+        let span = self.peek_token()?.0;
         self.pop_state();
         self.skip();
-        Ok((Event::MappingEnd, marker))
+        Ok((Event::MappingEnd, span))
     }
 
     fn flow_mapping_value(&mut self, empty: bool) -> ParseResult {
-        let mark: Marker = {
+        let span: Span = {
             if empty {
-                let Token(mark, _) = *self.peek_token()?;
+                let Token(span, _) = *self.peek_token()?;
                 self.state = State::FlowMappingKey;
-                return Ok((empty_scalar(), mark));
+                return Ok((empty_scalar(), Span::empty(span.start)));
             } else {
                 match *self.peek_token()? {
-                    Token(marker, TokenType::Value) => {
+                    Token(span, TokenType::Value) => {
                         self.skip();
                         match self.peek_token()?.1 {
                             TokenType::FlowEntry | TokenType::FlowMappingEnd => {}
@@ -594,15 +885,15 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                                 return self.parse_node(false, false);
                             }
                         }
-                        marker
+                        Span::empty(span.start)
                     }
-                    Token(marker, _) => marker,
+                    Token(span, _) => Span::empty(span.start),
                 }
             }
         };
 
         self.state = State::FlowMappingKey;
-        Ok((empty_scalar(), mark))
+        Ok((empty_scalar(), span))
     }
 
     fn flow_sequence_entry(&mut self, first: bool) -> ParseResult {
@@ -612,32 +903,34 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
             self.skip();
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::FlowSequenceEnd) => {
+            Token(span, TokenType::FlowSequenceEnd) => {
                 self.pop_state();
                 self.skip();
-                return Ok((Event::SequenceEnd, mark));
+                return Ok((Event::SequenceEnd, span));
             }
             Token(_, TokenType::FlowEntry) if !first => {
                 self.skip();
             }
-            Token(mark, _) if !first => {
-                return Err(ScanError::new(
-                    mark,
+            Token(span, ref token) if !first => {
+                return Err(unexpected_token(
+                    token,
+                    ErrorKind::Syntax,
+                    span,
                     "while parsing a flow sequence, expected ',' or ']'",
                 ));
             }
             _ => { /* next */ }
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::FlowSequenceEnd) => {
+            Token(span, TokenType::FlowSequenceEnd) => {
                 self.pop_state();
                 self.skip();
-                Ok((Event::SequenceEnd, mark))
+                Ok((Event::SequenceEnd, span))
             }
-            Token(mark, TokenType::Key) => {
+            Token(span, TokenType::Key) => {
                 self.state = State::FlowSequenceEntryMappingKey;
                 self.skip();
-                Ok((Event::MappingStart(0), mark))
+                Ok((Event::MappingStart(0, None), span))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntry);
@@ -649,19 +942,19 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
     fn indentless_sequence_entry(&mut self) -> ParseResult {
         match *self.peek_token()? {
             Token(_, TokenType::BlockEntry) => (),
-            Token(mark, _) => {
+            Token(..) => {
                 self.pop_state();
-                return Ok((Event::SequenceEnd, mark));
+                return Ok((Event::SequenceEnd, Span::empty(self.last_end)));
             }
         }
         self.skip();
         match *self.peek_token()? {
-            Token(mark, TokenType::BlockEntry)
-            | Token(mark, TokenType::Key)
-            | Token(mark, TokenType::Value)
-            | Token(mark, TokenType::BlockEnd) => {
+            Token(span, TokenType::BlockEntry)
+            | Token(span, TokenType::Key)
+            | Token(span, TokenType::Value)
+            | Token(span, TokenType::BlockEnd) => {
                 self.state = State::IndentlessSequenceEntry;
-                Ok((empty_scalar(), mark))
+                Ok((empty_scalar(), Span::empty(span.start)))
             }
             _ => {
                 self.push_state(State::IndentlessSequenceEntry);
@@ -677,17 +970,17 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
             self.skip();
         }
         match *self.peek_token()? {
-            Token(mark, TokenType::BlockEnd) => {
+            Token(_, TokenType::BlockEnd) => {
                 self.pop_state();
                 self.skip();
-                Ok((Event::SequenceEnd, mark))
+                Ok((Event::SequenceEnd, Span::empty(self.last_end)))
             }
             Token(_, TokenType::BlockEntry) => {
                 self.skip();
                 match *self.peek_token()? {
-                    Token(mark, TokenType::BlockEntry) | Token(mark, TokenType::BlockEnd) => {
+                    Token(span, TokenType::BlockEntry) | Token(span, TokenType::BlockEnd) => {
                         self.state = State::BlockSequenceEntry;
-                        Ok((empty_scalar(), mark))
+                        Ok((empty_scalar(), Span::empty(span.start)))
                     }
                     _ => {
                         self.push_state(State::BlockSequenceEntry);
@@ -695,8 +988,10 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                     }
                 }
             }
-            Token(mark, _) => Err(ScanError::new(
-                mark,
+            Token(span, ref token) => Err(unexpected_token(
+                token,
+                ErrorKind::BadIndentation,
+                span,
                 "while parsing a block collection, did not find expected '-' indicator",
             )),
         }
@@ -704,12 +999,12 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
 
     fn flow_sequence_entry_mapping_key(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token(mark, TokenType::Value)
-            | Token(mark, TokenType::FlowEntry)
-            | Token(mark, TokenType::FlowSequenceEnd) => {
+            Token(span, TokenType::Value)
+            | Token(span, TokenType::FlowEntry)
+            | Token(span, TokenType::FlowSequenceEnd) => {
                 self.skip();
                 self.state = State::FlowSequenceEntryMappingValue;
-                Ok((empty_scalar(), mark))
+                Ok((empty_scalar(), Span::empty(span.start)))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntryMappingValue);
@@ -724,9 +1019,9 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                 self.skip();
                 self.state = State::FlowSequenceEntryMappingValue;
                 match *self.peek_token()? {
-                    Token(mark, TokenType::FlowEntry) | Token(mark, TokenType::FlowSequenceEnd) => {
+                    Token(span, TokenType::FlowEntry) | Token(span, TokenType::FlowSequenceEnd) => {
                         self.state = State::FlowSequenceEntryMappingEnd;
-                        Ok((empty_scalar(), mark))
+                        Ok((empty_scalar(), Span::empty(span.start)))
                     }
                     _ => {
                         self.push_state(State::FlowSequenceEntryMappingEnd);
@@ -734,37 +1029,95 @@ impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
                     }
                 }
             }
-            Token(mark, _) => {
+            Token(span, _) => {
                 self.state = State::FlowSequenceEntryMappingEnd;
-                Ok((empty_scalar(), mark))
+                Ok((empty_scalar(), Span::empty(span.start)))
             }
         }
     }
 
     fn flow_sequence_entry_mapping_end(&mut self) -> ParseResult {
         self.state = State::FlowSequenceEntry;
-        Ok((Event::MappingEnd, self.scanner.get_mark()))
+        Ok((Event::MappingEnd, Span::empty(self.last_end)))
     }
 }
 
-#[cfg(test)]
-impl<'re, T: Iterator<Item = char>, R: EventReceiver> Parser<'re, T, R> {
-    fn peek(&mut self) -> Result<&(Event, Marker), ScanError> {
-        match self.current {
-            Some(ref x) => Ok(x),
-            None => {
-                self.current = Some(self.next()?);
-                self.peek()
+impl<'re, 'd, Rd: io::Read, R: TryEventReceiver> Parser<'re, &'d mut Decoder<Rd>, R> {
+    /// Parser of the chars of `decoder`, e.g. to parse a file whatever its
+    /// encoding. Errors of the decoder are reported as parse errors.
+    pub fn from_decoder(
+        decoder: &'d mut Decoder<Rd>,
+        recv: &'re mut R,
+        with_comments: bool,
+    ) -> Self {
+        Parser::from_scanner(Scanner::from_decoder(decoder, with_comments), Some(recv))
+    }
+}
+
+impl<'re, T: Iterator<Item = char>, R: TryEventReceiver> Iterator for Parser<'re, T, R> {
+    type Item = ParseResult;
+
+    fn next(&mut self) -> Option<ParseResult> {
+        if self.finished {
+            return None;
+        }
+        if let Some(comment) = self.comments.pop_front() {
+            return Some(Ok(comment));
+        }
+        let event = match self.current.take() {
+            None => self.parse(),
+            Some(event) => Ok(event),
+        };
+        match event {
+            Ok(event) => match self.comments.pop_front() {
+                Some(comment) => {
+                    self.current = Some(event);
+                    Some(Ok(comment))
+                }
+                None => {
+                    self.finished = event.0 == Event::StreamEnd;
+                    Some(Ok(event))
+                }
+            },
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
             }
         }
     }
 }
 
+// error found at `token`, which is reported as an unexpected end of stream if
+// `token` ends the stream
+fn unexpected_token(token: &TokenType, kind: ErrorKind, span: Span, info: &str) -> ScanError {
+    let kind = match *token {
+        TokenType::StreamEnd => ErrorKind::UnexpectedEof,
+        _ => kind,
+    };
+    ScanError::new(span.start, info)
+        .with_span(span)
+        .with_kind(kind)
+}
+
+fn default_tag_directives() -> HashMap<String, String> {
+    let mut tag_directives = HashMap::new();
+    tag_directives.insert("!".to_owned(), "!".to_owned());
+    tag_directives.insert("!!".to_owned(), "tag:yaml.org,2002:".to_owned());
+    tag_directives
+}
+
 #[cfg(test)]
 mod test {
     use super::Event;
     use super::EventReceiver;
     use super::Parser;
+    use super::Span;
+    use super::SpannedEventReceiver;
+    use super::TryEventReceiver;
+    use super::DEFAULT_MAX_DEPTH;
+    use crate::scanner::Decoder;
+    use crate::scanner::ErrorKind;
+    use crate::scanner::ScanError;
 
     struct NoOpRecv {}
 
@@ -792,9 +1145,215 @@ a5: *x
         let mut p = Parser::new(s.chars(), &mut recv, true);
         while {
             let event_peek = p.peek().unwrap().clone();
-            let event = p.next().unwrap();
+            let event = p.next().unwrap().unwrap();
             assert_eq!(event, event_peek);
             event.0 != Event::StreamEnd
         } {}
     }
+
+    #[test]
+    fn test_iterator() {
+        let s = "# head\na: 1 # one\nb: [2, 3]\n";
+        let events: Vec<_> = Parser::events(s.chars(), true)
+            .map(|ev| ev.unwrap().0)
+            .collect();
+        assert_eq!(events.len(), 15);
+        assert!(matches!(events[1], Event::Comment(ref c, _) if c == " head"));
+        assert!(matches!(events[5], Event::Scalar(ref v, ..) if v == "1"));
+        assert_eq!(events[6], Event::Comment(" one".to_owned(), true));
+        assert_eq!(events[14], Event::StreamEnd);
+
+        // stops early, and after errors
+        let mut p = Parser::events("[a, b".chars(), false);
+        assert_eq!(p.by_ref().take(3).count(), 3);
+        let rest: Vec<_> = p.collect();
+        assert_eq!(rest.len(), 3);
+        assert!(rest[2].is_err());
+
+        // anchors are scoped to their document
+        let s = "a: &x 1\n---\nb: *x\n";
+        let err = Parser::events(s.chars(), false)
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnknownAnchor);
+    }
+
+    struct TagFilter {
+        scalars: Vec<String>,
+    }
+
+    impl TryEventReceiver for TagFilter {
+        fn on_event(&mut self, ev: Event, span: Span) -> Result<(), ScanError> {
+            match ev {
+                Event::Scalar(_, _, _, Some(_)) => {
+                    Err(ScanError::new(span.start, "tags are not allowed"))
+                }
+                Event::Scalar(v, ..) => {
+                    self.scalars.push(v);
+                    Ok(())
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_receiver() {
+        let s = "- a\n- !!str b\n- c\n";
+        let mut recv = TagFilter {
+            scalars: Vec::new(),
+        };
+        let err = Parser::new(s.chars(), &mut recv, false)
+            .load(false)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "tags are not allowed at line 2 column 9");
+        assert_eq!(recv.scalars, vec!["a"]);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let s = "- - a\n  - [b, {c: [d]}]\n";
+        assert!(Parser::events(s.chars(), false)
+            .max_depth(5)
+            .load(false)
+            .is_ok());
+        let err = Parser::events(s.chars(), false)
+            .max_depth(4)
+            .load(false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while parsing a node, exceeded the maximum nesting depth of 4 at line 2 column 13"
+        );
+
+        let s = "- ".repeat(100_000) + "a";
+        assert!(Parser::events(s.chars(), false).load(false).is_err());
+        let s = "[".repeat(DEFAULT_MAX_DEPTH) + &"]".repeat(DEFAULT_MAX_DEPTH);
+        assert!(Parser::events(s.chars(), false).load(false).is_ok());
+    }
+
+    #[test]
+    fn test_decoder_errors() {
+        let bytes = b"a: 1\nb: \xff\n";
+        let mut decoder = Decoder::new(&bytes[..]).unwrap();
+        let mut recv = NoOpRecv {};
+        let err = Parser::from_decoder(&mut decoder, &mut recv, false)
+            .load(false)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidEncoding);
+        assert_eq!(
+            err.to_string(),
+            "while decoding the stream, found invalid UTF-8 at line 2 column 4, while parsing a \
+             block mapping started at line 1 column 1"
+        );
+    }
+
+    #[derive(Default)]
+    struct WarningRecv {
+        versions: Vec<Option<(u32, u32)>>,
+        warnings: Vec<String>,
+    }
+
+    impl EventReceiver for WarningRecv {
+        fn on_event(&mut self, ev: Event, _mark: crate::scanner::Marker) {
+            if let Event::DocumentStart(version) = ev {
+                self.versions.push(version);
+            }
+        }
+
+        fn on_warning(&mut self, _mark: crate::scanner::Marker, message: &str) {
+            self.warnings.push(message.to_owned());
+        }
+    }
+
+    #[test]
+    fn test_version_directive() {
+        let s = "%YAML 1.1\n--- a\n...\n%YAML 1.3\n--- b\n...\n--- c\n";
+        let mut recv = WarningRecv::default();
+        Parser::new(s.chars(), &mut recv, false).load(true).unwrap();
+        assert_eq!(recv.versions, vec![Some((1, 1)), Some((1, 3)), None]);
+        assert_eq!(
+            recv.warnings,
+            vec!["found YAML 1.3 document, parsing it as YAML 1.2"]
+        );
+
+        for s in &["%YAML 1.2\n%YAML 1.2\n--- a\n", "%YAML 2.0\n--- a\n"] {
+            let mut recv = WarningRecv::default();
+            assert!(Parser::new(s.chars(), &mut recv, false).load(true).is_err());
+        }
+    }
+
+    struct SpanRecv {
+        spans: Vec<(Event, Span)>,
+    }
+
+    impl SpannedEventReceiver for SpanRecv {
+        fn on_event(&mut self, ev: Event, span: Span) {
+            self.spans.push((ev, span));
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let s = "a: |\n  x\n  y\n\nb: [c , d]\ne: f g  \n";
+        let mut recv = SpanRecv { spans: Vec::new() };
+        Parser::new(s.chars(), &mut recv, false)
+            .load(false)
+            .unwrap();
+        let spans: Vec<_> = recv
+            .spans
+            .iter()
+            .map(|(_, span)| (span.start.index, span.end.index))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 0),   // StreamStart
+                (0, 0),   // DocumentStart
+                (0, 0),   // MappingStart
+                (0, 1),   // a
+                (3, 14),  // "x\ny\n", up to the next key
+                (14, 15), // b
+                (17, 18), // [
+                (18, 19), // c
+                (22, 23), // d
+                (23, 24), // ]
+                (25, 26), // e
+                (28, 31), // "f g", without trailing blanks
+                (31, 31), // MappingEnd, after the last value
+                (34, 34), // DocumentEnd
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tag_directives() {
+        let s = "%TAG !e! tag:example.com,2000:app/\n---\n- !e!foo a\n- !!int 1\n";
+        let mut recv = NoOpRecv {};
+        let mut p = Parser::new(s.chars(), &mut recv, true);
+        let mut uris = Vec::new();
+        loop {
+            match p.next().unwrap().unwrap().0 {
+                Event::Scalar(_, _, _, Some(tag)) => uris.push(tag.uri),
+                Event::StreamEnd => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            uris,
+            vec!["tag:example.com,2000:app/foo", "tag:yaml.org,2002:int"]
+        );
+    }
+
+    #[test]
+    fn test_tag_directive_errors() {
+        for s in &[
+            "%TAG ! !foo\n%TAG ! !foo\n--- bar\n",
+            "%TAG !h! tag:example.com,2000:\n--- !h!a\n...\n--- !h!b\n",
+        ] {
+            let mut recv = NoOpRecv {};
+            let mut p = Parser::new(s.chars(), &mut recv, true);
+            assert!(p.load(true).is_err());
+        }
+    }
 }
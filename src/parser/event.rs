@@ -1,22 +1,37 @@
 use crate::scanner::TScalarStyle;
-use crate::scanner::TokenType;
 
 pub type AnchorID = usize;
 
+/// Tag of a node, resolved against the `%TAG` directives of its document.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Tag {
+    /// Handle as written, e.g. `!`, `!!` or `!e!`. Empty for verbatim tags
+    /// and the non-specific `!` tag.
+    pub handle: String,
+    /// Suffix as written, e.g. `int` in `!!int`.
+    pub suffix: String,
+    /// Resolved tag, e.g. `tag:yaml.org,2002:int` for `!!int`, or `!` for the
+    /// non-specific tag.
+    pub uri: String,
+}
+
 /// `Event` is used with the low-level event base parsing API,
 /// see `EventReceiver` trait.
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub enum Event {
     StreamStart,
     StreamEnd,
-    DocumentStart,
+    /// version given by the `%YAML` directive, if any, as (major, minor)
+    DocumentStart(Option<(u32, u32)>),
     DocumentEnd,
     Alias(AnchorID),
     /// value, style, anchor_id, tag
-    Scalar(String, TScalarStyle, AnchorID, Option<TokenType>),
-    SequenceStart(AnchorID),
+    Scalar(String, TScalarStyle, AnchorID, Option<Tag>),
+    /// anchor_id, tag
+    SequenceStart(AnchorID, Option<Tag>),
     SequenceEnd,
-    MappingStart(AnchorID),
+    /// anchor_id, tag
+    MappingStart(AnchorID, Option<Tag>),
     MappingEnd,
     // comment, inline
     Comment(String, bool),
@@ -27,6 +42,6 @@ pub fn empty_scalar() -> Event {
     Event::Scalar("~".to_owned(), TScalarStyle::Plain, 0, None)
 }
 
-pub fn empty_scalar_with_anchor(anchor: usize, tag: Option<TokenType>) -> Event {
+pub fn empty_scalar_with_anchor(anchor: usize, tag: Option<Tag>) -> Event {
     Event::Scalar("".to_owned(), TScalarStyle::Plain, anchor, tag)
 }
@@ -1,8 +1,15 @@
 use std::char;
 use std::collections::VecDeque;
+use std::io;
 
+pub(crate) use self::decoder::read_error;
+pub use self::decoder::Decoder;
+pub use self::error::Diagnostic;
+pub use self::error::ErrorKind;
 pub use self::error::ScanError;
 pub use self::marker::Marker;
+pub(crate) use self::marker::Position;
+pub use self::marker::Span;
 use self::types::SimpleKey;
 pub use self::types::TEncoding;
 pub use self::types::TScalarStyle;
@@ -10,6 +17,7 @@ pub use self::types::Token;
 pub use self::types::TokenType;
 use funcs::*;
 
+mod decoder;
 mod error;
 mod funcs;
 mod marker;
@@ -20,6 +28,9 @@ pub type ScanResult = Result<(), ScanError>;
 #[derive(Debug)]
 pub struct Scanner<T> {
     rdr: T,
+    // error of `rdr`, which stops where it fails
+    rdr_error: Option<fn(&T) -> Option<ScanError>>,
+    encoding: TEncoding,
     mark: Marker,
     tokens: VecDeque<Token>,
     buffer: VecDeque<char>,
@@ -33,7 +44,7 @@ pub struct Scanner<T> {
     simple_keys: Vec<SimpleKey>,
     indent: isize,
     indents: Vec<isize>,
-    flow_level: u8,
+    flow_level: usize,
     tokens_parsed: usize,
     token_available: bool,
 
@@ -60,11 +71,26 @@ impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
     }
 }
 
+impl<'d, R: io::Read> Scanner<&'d mut Decoder<R>> {
+    /// Creates the YAML tokenizer of the chars of `decoder`, reporting their
+    /// encoding in the `StreamStart` token. Errors of the decoder are
+    /// reported in place of the tokens following them.
+    pub fn from_decoder(decoder: &'d mut Decoder<R>, with_comments: bool) -> Self {
+        let encoding = decoder.encoding();
+        let mut scanner = Scanner::new(decoder, with_comments);
+        scanner.encoding = encoding;
+        scanner.rdr_error = Some(|decoder| decoder.error().cloned());
+        scanner
+    }
+}
+
 impl<T: Iterator<Item = char>> Scanner<T> {
     /// Creates the YAML tokenizer.
     pub fn new(rdr: T, with_comments: bool) -> Scanner<T> {
         Scanner {
             rdr,
+            rdr_error: None,
+            encoding: TEncoding::Utf8,
             buffer: VecDeque::new(),
             mark: Marker::new(0, 1, 0),
             tokens: VecDeque::new(),
@@ -139,7 +165,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             .expect("cannot skip: ensure lookahead is called");
 
         self.mark.index += 1;
+        self.mark.byte_offset += c.len_utf8();
+        // a CRLF line break ends the line at its '\n'
+        if c == '\r' {
+            self.lookahead(1);
+        }
         match c {
+            '\r' if self.buffer[0] == '\n' => self.mark.col += 1,
             '\n' | '\r' => {
                 self.mark.line += 1;
                 self.mark.col = 0;
@@ -269,7 +301,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             '%' | '@' | '`' => Err(ScanError::new(
                 self.mark,
                 &format!("unexpected character: `{}'", ch),
-            )),
+            )
+            .with_kind(ErrorKind::Syntax)),
             _ => self.fetch_plain_scalar(),
         }
     }
@@ -282,6 +315,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if !self.token_available {
             self.fetch_more_tokens()?;
         }
+        if let Some(e) = self.rdr_error.and_then(|rdr_error| rdr_error(&self.rdr)) {
+            return Err(e);
+        }
 
         let token = self.tokens.pop_front().unwrap();
         self.token_available = false;
@@ -324,7 +360,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 && (sk.mark.line < self.mark.line || sk.mark.index + 1024 < self.mark.index)
             {
                 if sk.required {
-                    return Err(ScanError::new(self.mark, "simple key expect ':'"));
+                    return Err(ScanError::new(self.mark, "simple key expect ':'")
+                        .with_kind(ErrorKind::Syntax));
                 }
                 sk.possible = false;
             }
@@ -362,8 +399,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.indent = -1;
         self.stream_start_produced = true;
         self.allow_simple_key();
-        self.tokens
-            .push_back(Token(mark, TokenType::StreamStart(TEncoding::Utf8)));
+        self.tokens.push_back(Token(
+            Span::empty(mark),
+            TokenType::StreamStart(self.encoding),
+        ));
         self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
     }
 
@@ -379,7 +418,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.disallow_simple_key();
 
         self.tokens
-            .push_back(Token(self.mark, TokenType::StreamEnd));
+            .push_back(Token(Span::empty(self.mark), TokenType::StreamEnd));
         Ok(())
     }
 
@@ -414,7 +453,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 }
                 // XXX return an empty TagDirective token
                 Token(
-                    start_mark,
+                    Span::new(start_mark, self.mark),
                     TokenType::TagDirective(String::new(), String::new()),
                 )
                 // return Err(ScanError::new(start_mark,
@@ -439,14 +478,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Err(ScanError::new(
                 *mark,
                 "while scanning a YAML directive, did not find expected digit or '.' character",
-            ));
+            )
+            .with_kind(ErrorKind::InvalidDirective));
         }
 
         self.skip();
 
         let minor = self.scan_version_directive_number(mark)?;
 
-        Ok(Token(*mark, TokenType::VersionDirective(major, minor)))
+        Ok(Token(
+            Span::new(*mark, self.mark),
+            TokenType::VersionDirective(major, minor),
+        ))
     }
 
     fn scan_directive_name(&mut self) -> Result<String, ScanError> {
@@ -463,14 +506,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Err(ScanError::new(
                 start_mark,
                 "while scanning a directive, could not find expected directive name",
-            ));
+            )
+            .with_kind(ErrorKind::InvalidDirective));
         }
 
         if !is_blankz(self.ch()) {
             return Err(ScanError::new(
                 start_mark,
                 "while scanning a directive, found unexpected non-alphabetical character",
-            ));
+            )
+            .with_kind(ErrorKind::InvalidDirective));
         }
 
         Ok(string)
@@ -485,7 +530,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     *mark,
                     "while scanning a YAML directive, found extremely long version number",
-                ));
+                )
+                .with_kind(ErrorKind::InvalidDirective));
             }
             length += 1;
             val = val * 10 + ((self.ch() as u32) - ('0' as u32));
@@ -497,7 +543,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Err(ScanError::new(
                 *mark,
                 "while scanning a YAML directive, did not find expected version number",
-            ));
+            )
+            .with_kind(ErrorKind::InvalidDirective));
         }
 
         Ok(val)
@@ -525,12 +572,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.lookahead(1);
 
         if is_blankz(self.ch()) {
-            Ok(Token(*mark, TokenType::TagDirective(handle, prefix)))
+            Ok(Token(
+                Span::new(*mark, self.mark),
+                TokenType::TagDirective(handle, prefix),
+            ))
         } else {
             Err(ScanError::new(
                 *mark,
                 "while scanning TAG, did not find expected whitespace or line break",
-            ))
+            )
+            .with_kind(ErrorKind::InvalidDirective))
         }
     }
 
@@ -562,7 +613,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     start_mark,
                     "while scanning a tag, did not find the expected '>'",
-                ));
+                )
+                .with_kind(ErrorKind::InvalidTag));
             }
 
             self.skip();
@@ -590,12 +642,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.lookahead(1);
         if is_blankz(self.ch()) {
             // XXX: ex 7.2, an empty scalar can follow a secondary tag
-            Ok(Token(start_mark, TokenType::Tag(handle, suffix)))
+            Ok(Token(
+                Span::new(start_mark, self.mark),
+                TokenType::Tag(handle, suffix),
+            ))
         } else {
             Err(ScanError::new(
                 start_mark,
                 "while scanning a tag, did not find expected whitespace or line break",
-            ))
+            )
+            .with_kind(ErrorKind::InvalidTag))
         }
     }
 
@@ -603,10 +659,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let mut string = String::new();
         self.lookahead(1);
         if self.ch() != '!' {
-            return Err(ScanError::new(
-                *mark,
-                "while scanning a tag, did not find expected '!'",
-            ));
+            return Err(
+                ScanError::new(*mark, "while scanning a tag, did not find expected '!'")
+                    .with_kind(ErrorKind::InvalidTag),
+            );
         }
 
         string.push(self.ch());
@@ -630,7 +686,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Err(ScanError::new(
                 *mark,
                 "while parsing a tag directive, did not find expected '!'",
-            ));
+            )
+            .with_kind(ErrorKind::InvalidDirective));
         }
         Ok(string)
     }
@@ -682,7 +739,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Err(ScanError::new(
                 *mark,
                 "while parsing a tag, did not find expected tag URI",
-            ));
+            )
+            .with_kind(ErrorKind::InvalidTag));
         }
 
         Ok(string)
@@ -698,7 +756,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     *mark,
                     "while parsing a tag, did not find URI escaped octet",
-                ));
+                )
+                .with_kind(ErrorKind::InvalidEscape));
             }
 
             let octet = (as_hex(self.buffer[1]) << 4) + as_hex(self.buffer[2]);
@@ -712,7 +771,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         return Err(ScanError::new(
                             *mark,
                             "while parsing a tag, found an incorrect leading UTF-8 octet",
-                        ));
+                        )
+                        .with_kind(ErrorKind::InvalidEscape));
                     }
                 };
                 code = octet;
@@ -721,7 +781,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     return Err(ScanError::new(
                         *mark,
                         "while parsing a tag, found an incorrect trailing UTF-8 octet",
-                    ));
+                    )
+                    .with_kind(ErrorKind::InvalidEscape));
                 }
                 code = (code << 8) + octet;
             }
@@ -741,7 +802,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             None => Err(ScanError::new(
                 *mark,
                 "while parsing a tag, found an invalid UTF-8 codepoint",
-            )),
+            )
+            .with_kind(ErrorKind::InvalidEscape)),
         }
     }
 
@@ -779,13 +841,20 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 start_mark,
                 "while scanning an anchor or alias, did not find expected alphabetic or numeric \
                  character",
-            ));
+            )
+            .with_kind(ErrorKind::Syntax));
         }
 
         if alias {
-            Ok(Token(start_mark, TokenType::Alias(string)))
+            Ok(Token(
+                Span::new(start_mark, self.mark),
+                TokenType::Alias(string),
+            ))
         } else {
-            Ok(Token(start_mark, TokenType::Anchor(string)))
+            Ok(Token(
+                Span::new(start_mark, self.mark),
+                TokenType::Anchor(string),
+            ))
         }
     }
 
@@ -793,14 +862,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         // The indicators '[' and '{' may start a simple key.
         self.save_simple_key()?;
 
-        self.increase_flow_level()?;
+        self.increase_flow_level();
 
         self.allow_simple_key();
 
         let start_mark = self.mark;
         self.skip();
 
-        self.tokens.push_back(Token(start_mark, tok));
+        self.tokens
+            .push_back(Token(Span::new(start_mark, self.mark), tok));
         Ok(())
     }
 
@@ -813,7 +883,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         self.skip();
 
-        self.tokens.push_back(Token(start_mark, tok));
+        self.tokens
+            .push_back(Token(Span::new(start_mark, self.mark), tok));
         Ok(())
     }
 
@@ -824,18 +895,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         self.skip();
 
-        self.tokens
-            .push_back(Token(start_mark, TokenType::FlowEntry));
+        self.tokens.push_back(Token(
+            Span::new(start_mark, self.mark),
+            TokenType::FlowEntry,
+        ));
         Ok(())
     }
 
-    fn increase_flow_level(&mut self) -> ScanResult {
+    fn increase_flow_level(&mut self) {
         self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
-        self.flow_level = self
-            .flow_level
-            .checked_add(1)
-            .ok_or_else(|| ScanError::new(self.mark, "recursion limit exceeded"))?;
-        Ok(())
+        self.flow_level += 1;
     }
 
     fn decrease_flow_level(&mut self) {
@@ -852,7 +921,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     self.mark,
                     "block sequence entries are not allowed in this context",
-                ));
+                )
+                .with_kind(ErrorKind::Syntax));
             }
 
             let mark = self.mark;
@@ -860,10 +930,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.roll_indent(mark.col, None, TokenType::BlockSequenceStart, mark);
         } else {
             // - * only allowed in block
-            return Err(ScanError::new(
-                self.mark,
-                r#""-" is only valid inside a block"#,
-            ));
+            return Err(
+                ScanError::new(self.mark, r#""-" is only valid inside a block"#)
+                    .with_kind(ErrorKind::Syntax),
+            );
         }
         self.remove_simple_key()?;
         self.allow_simple_key();
@@ -871,8 +941,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         self.skip();
 
-        self.tokens
-            .push_back(Token(start_mark, TokenType::BlockEntry));
+        self.tokens.push_back(Token(
+            Span::new(start_mark, self.mark),
+            TokenType::BlockEntry,
+        ));
         Ok(())
     }
 
@@ -887,7 +959,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.skip();
         self.skip();
 
-        self.tokens.push_back(Token(mark, t));
+        self.tokens.push_back(Token(Span::new(mark, self.mark), t));
         Ok(())
     }
 
@@ -929,7 +1001,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     return Err(ScanError::new(
                         start_mark,
                         "while scanning a block scalar, found an indentation indicator equal to 0",
-                    ));
+                    )
+                    .with_kind(ErrorKind::BadIndentation));
                 }
                 increment = (self.ch() as usize) - ('0' as usize);
                 self.skip();
@@ -939,7 +1012,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     start_mark,
                     "while scanning a block scalar, found an indentation indicator equal to 0",
-                ));
+                )
+                .with_kind(ErrorKind::BadIndentation));
             }
 
             increment = (self.ch() as usize) - ('0' as usize);
@@ -975,7 +1049,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Err(ScanError::new(
                 start_mark,
                 "while scanning a block scalar, did not find expected comment or line break",
-            ));
+            )
+            .with_kind(ErrorKind::Syntax));
         }
 
         if is_break(self.ch()) {
@@ -995,8 +1070,6 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         self.lookahead(1);
 
-        let start_mark = self.mark;
-
         while self.mark.col == indent && !is_z(self.ch()) {
             // We are at the beginning of a non-empty line.
             trailing_blank = is_blank(self.ch());
@@ -1043,12 +1116,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         if literal {
             Ok(Token(
-                start_mark,
+                Span::new(start_mark, self.mark),
                 TokenType::Scalar(TScalarStyle::Literal, string),
             ))
         } else {
             Ok(Token(
-                start_mark,
+                Span::new(start_mark, self.mark),
                 TokenType::Scalar(TScalarStyle::Folded, string),
             ))
         }
@@ -1073,7 +1146,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     self.mark,
                     "while scanning a block scalar, found a tab character where an indentation \
                      space is expected",
-                ));
+                )
+                .with_kind(ErrorKind::TabIndentation));
             }
 
             if !is_break(self.ch()) {
@@ -1138,14 +1212,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     start_mark,
                     "while scanning a quoted scalar, found unexpected document indicator",
-                ));
+                )
+                .with_kind(ErrorKind::Syntax));
             }
 
             if is_z(self.ch()) {
                 return Err(ScanError::new(
                     start_mark,
                     "while scanning a quoted scalar, found unexpected end of stream",
-                ));
+                )
+                .with_kind(ErrorKind::UnexpectedEof));
             }
 
             self.lookahead(2);
@@ -1204,7 +1280,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                                 return Err(ScanError::new(
                                     start_mark,
                                     "while parsing a quoted scalar, found unknown escape character",
-                                ))
+                                )
+                                .with_kind(ErrorKind::InvalidEscape))
                             }
                         }
                         self.skip();
@@ -1219,7 +1296,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                                         start_mark,
                                         "while parsing a quoted scalar, did not find expected \
                                          hexadecimal number",
-                                    ));
+                                    )
+                                    .with_kind(ErrorKind::InvalidEscape));
                                 }
                                 value = (value << 4) + as_hex(self.buffer[i]);
                             }
@@ -1231,7 +1309,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                                         start_mark,
                                         "while parsing a quoted scalar, found invalid Unicode \
                                          character escape code",
-                                    ));
+                                    )
+                                    .with_kind(ErrorKind::InvalidEscape));
                                 }
                             };
                             string.push(ch);
@@ -1305,12 +1384,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         if single {
             Ok(Token(
-                start_mark,
+                Span::new(start_mark, self.mark),
                 TokenType::Scalar(TScalarStyle::SingleQuoted, string),
             ))
         } else {
             Ok(Token(
-                start_mark,
+                Span::new(start_mark, self.mark),
                 TokenType::Scalar(TScalarStyle::DoubleQuoted, string),
             ))
         }
@@ -1329,6 +1408,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn scan_plain_scalar(&mut self) -> Result<Token, ScanError> {
         let indent = self.indent + 1;
         let start_mark = self.mark;
+        let mut end_mark = self.mark;
 
         let mut string = String::new();
         let mut leading_break = String::new();
@@ -1394,6 +1474,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
                 string.push(self.ch());
                 self.skip();
+                end_mark = self.mark;
                 self.lookahead(2);
             }
             // is the end?
@@ -1408,7 +1489,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         return Err(ScanError::new(
                             start_mark,
                             "while scanning a plain scalar, found a tab",
-                        ));
+                        )
+                        .with_kind(ErrorKind::TabIndentation));
                     }
 
                     if leading_blanks {
@@ -1442,7 +1524,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         Ok(Token(
-            start_mark,
+            Span::new(start_mark, end_mark),
             TokenType::Scalar(TScalarStyle::Plain, string),
         ))
     }
@@ -1455,7 +1537,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 return Err(ScanError::new(
                     self.mark,
                     "mapping keys are not allowed in this context",
-                ));
+                )
+                .with_kind(ErrorKind::Syntax));
             }
             self.roll_indent(
                 start_mark.col,
@@ -1474,7 +1557,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         self.skip();
-        self.tokens.push_back(Token(start_mark, TokenType::Key));
+        self.tokens
+            .push_back(Token(Span::new(start_mark, self.mark), TokenType::Key));
         Ok(())
     }
 
@@ -1483,7 +1567,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         if sk.possible {
             // insert simple key
-            let tok = Token(sk.mark, TokenType::Key);
+            let tok = Token(Span::empty(sk.mark), TokenType::Key);
             let tokens_parsed = self.tokens_parsed;
             self.insert_token(sk.token_number - tokens_parsed, tok);
 
@@ -1492,7 +1576,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 sk.mark.col,
                 Some(sk.token_number),
                 TokenType::BlockMappingStart,
-                start_mark,
+                sk.mark,
             );
 
             self.simple_keys.last_mut().unwrap().possible = false;
@@ -1504,7 +1588,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     return Err(ScanError::new(
                         start_mark,
                         "mapping values are not allowed in this context",
-                    ));
+                    )
+                    .with_kind(ErrorKind::Syntax));
                 }
 
                 self.roll_indent(
@@ -1522,7 +1607,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
         }
         self.skip();
-        self.tokens.push_back(Token(start_mark, TokenType::Value));
+        self.tokens
+            .push_back(Token(Span::new(start_mark, self.mark), TokenType::Value));
 
         Ok(())
     }
@@ -1537,8 +1623,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.indent = col as isize;
             let tokens_parsed = self.tokens_parsed;
             match number {
-                Some(n) => self.insert_token(n - tokens_parsed, Token(mark, tok)),
-                None => self.tokens.push_back(Token(mark, tok)),
+                Some(n) => self.insert_token(n - tokens_parsed, Token(Span::empty(mark), tok)),
+                None => self.tokens.push_back(Token(Span::empty(mark), tok)),
             }
         }
     }
@@ -1548,7 +1634,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return;
         }
         while self.indent > col {
-            self.tokens.push_back(Token(self.mark, TokenType::BlockEnd));
+            self.tokens
+                .push_back(Token(Span::empty(self.mark), TokenType::BlockEnd));
             self.indent = self.indents.pop().unwrap();
         }
     }
@@ -1572,7 +1659,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn remove_simple_key(&mut self) -> ScanResult {
         let last = self.simple_keys.last_mut().unwrap();
         if last.possible && last.required {
-            return Err(ScanError::new(self.mark, "simple key expected"));
+            return Err(
+                ScanError::new(self.mark, "simple key expected").with_kind(ErrorKind::Syntax)
+            );
         }
 
         last.possible = false;
@@ -1594,7 +1683,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.lookahead(1);
         }
 
-        let token = Token(mark, TokenType::Comment(comment, !self.has_skipped_line));
+        let token = Token(
+            Span::new(mark, self.mark),
+            TokenType::Comment(comment, !self.has_skipped_line),
+        );
         self.tokens.push_back(token);
         Ok(())
     }
@@ -2134,6 +2226,44 @@ array: [
         end!(p);
     }
 
+    #[test]
+    fn test_stream_encoding() {
+        let bytes = b"\x00-\x00 \x00\xe9\x00\n";
+        let mut decoder = Decoder::new(&bytes[..]).unwrap();
+        let mut p = Scanner::from_decoder(&mut decoder, false);
+        next!(p, StreamStart(TEncoding::Utf16Be));
+        next!(p, BlockSequenceStart);
+        next!(p, BlockEntry);
+        next!(p, TScalarStyle::Plain, "é");
+        next!(p, BlockEnd);
+        next!(p, StreamEnd);
+        end!(p);
+
+        let mut p = get_scanner("a");
+        next!(p, StreamStart(TEncoding::Utf8));
+    }
+
+    #[test]
+    fn test_byte_offsets() {
+        let s = "clé: \"naïve 🦀\"\n¿: [ü]";
+        let p = Scanner::new(s.chars(), false);
+        let scalars: Vec<_> = p
+            .filter(|tok| matches!(tok.1, Scalar(..)))
+            .map(|tok| {
+                let (start, end) = (tok.0.start, tok.0.end);
+                assert!(start.byte_offset >= start.index);
+                &s[start.byte_offset..end.byte_offset]
+            })
+            .collect();
+        assert_eq!(scalars, vec!["clé", "\"naïve 🦀\"", "¿", "ü"]);
+
+        let mut p = Scanner::new(s.chars(), false);
+        let tok = p
+            .find(|tok| matches!(tok.1, Scalar(_, ref v) if v == "¿"))
+            .unwrap();
+        assert_eq!(tok.0.start, Marker::new(15, 2, 0).with_byte_offset(20));
+    }
+
     #[test]
     fn test_uri() {
         // TODO
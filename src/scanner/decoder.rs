@@ -0,0 +1,302 @@
+use std::io;
+use std::io::Read;
+use std::str;
+
+use super::ErrorKind;
+use super::Marker;
+use super::Position;
+use super::ScanError;
+use super::TEncoding;
+
+const BUFFER_SIZE: usize = 8192;
+
+/// Decoder of a byte stream into the chars the `Scanner` reads, detecting
+/// the encoding as described in the
+/// [YAML 1.2 spec](https://yaml.org/spec/1.2.2/#52-character-encodings):
+/// from a byte order mark, or else from the position of the zero bytes
+/// around the first character, defaulting to UTF-8.
+///
+/// Bytes are read and decoded as the scanner needs them. The decoder stops at
+/// the first invalid sequence or read error, which is then available from
+/// `error`: the stream otherwise looks as if it ended there.
+/// `Scanner::from_decoder` reports it as a scan error.
+///
+/// The markers of the decoder count the bytes of the decoded text as UTF-8,
+/// see `Marker::byte_offset`.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::scanner::{Decoder, TEncoding};
+///
+/// let bytes = b"\xff\xfea\x00:\x00 \x001\x00";
+/// let mut decoder = Decoder::new(&bytes[..]).unwrap();
+/// assert_eq!(decoder.encoding(), TEncoding::Utf16Le);
+/// assert_eq!(decoder.by_ref().collect::<String>(), "a: 1");
+/// assert!(decoder.error().is_none());
+/// ```
+#[derive(Debug)]
+pub struct Decoder<R> {
+    reader: R,
+    encoding: TEncoding,
+    buffer: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    // position of the next char, as the scanner counts it
+    position: Position,
+    error: Option<ScanError>,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Detect the encoding of `reader` from its first bytes, skipping the
+    /// byte order mark if any.
+    pub fn new(reader: R) -> io::Result<Decoder<R>> {
+        let mut decoder = Decoder {
+            reader,
+            encoding: TEncoding::Utf8,
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            position: Position::new(),
+            error: None,
+        };
+        // the first 4 bytes are enough to tell the encoding
+        while decoder.len < 4 {
+            match decoder.reader.read(&mut decoder.buffer[decoder.len..]) {
+                Ok(0) => break,
+                Ok(n) => decoder.len += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let (encoding, bom) = detect_encoding(&decoder.buffer[..decoder.len]);
+        decoder.encoding = encoding;
+        decoder.pos = bom;
+        Ok(decoder)
+    }
+
+    /// The encoding detected when the decoder was created.
+    pub fn encoding(&self) -> TEncoding {
+        self.encoding
+    }
+
+    /// The error the decoder stopped at, if any.
+    pub fn error(&self) -> Option<&ScanError> {
+        self.error.as_ref()
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        while self.pos == self.len {
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => return Ok(None),
+                Ok(n) => {
+                    self.pos = 0;
+                    self.len = n;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.pos += 1;
+        Ok(Some(self.buffer[self.pos - 1]))
+    }
+
+    // fill `bytes` up to the end of the stream, returning how many were read
+    fn next_bytes(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            match self.next_byte()? {
+                Some(b) => *byte = b,
+                None => return Ok(i),
+            }
+        }
+        Ok(bytes.len())
+    }
+
+    // Ok(None) at the end of the stream, Ok(Some(None)) for an invalid or
+    // truncated sequence
+    fn decode(&mut self) -> io::Result<Option<Option<char>>> {
+        let mut bytes = [0; 4];
+        match self.encoding {
+            TEncoding::Utf8 => {
+                let lead = match self.next_byte()? {
+                    Some(lead) => lead,
+                    None => return Ok(None),
+                };
+                bytes[0] = lead;
+                let len = match lead {
+                    0x00..=0x7f => return Ok(Some(Some(char::from(lead)))),
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => return Ok(Some(None)),
+                };
+                if self.next_bytes(&mut bytes[1..len])? < len - 1 {
+                    return Ok(Some(None));
+                }
+                Ok(Some(
+                    str::from_utf8(&bytes[..len])
+                        .ok()
+                        .and_then(|s| s.chars().next()),
+                ))
+            }
+            TEncoding::Utf16Le | TEncoding::Utf16Be => {
+                match self.next_bytes(&mut bytes[..2])? {
+                    0 => return Ok(None),
+                    1 => return Ok(Some(None)),
+                    _ => {}
+                }
+                let big_endian = self.encoding == TEncoding::Utf16Be;
+                let unit = |b: &[u8]| {
+                    if big_endian {
+                        u16::from_be_bytes([b[0], b[1]])
+                    } else {
+                        u16::from_le_bytes([b[0], b[1]])
+                    }
+                };
+                let high = unit(&bytes[..2]);
+                let mut len = 1;
+                // a high surrogate is followed by a low one
+                if (0xd800..0xdc00).contains(&high) {
+                    if self.next_bytes(&mut bytes[2..])? < 2 {
+                        return Ok(Some(None));
+                    }
+                    len = 2;
+                }
+                let units = [high, unit(&bytes[2..])];
+                let mut chars = char::decode_utf16(units[..len].iter().copied());
+                Ok(Some(chars.next().and_then(Result::ok)))
+            }
+            TEncoding::Utf32Le | TEncoding::Utf32Be => {
+                match self.next_bytes(&mut bytes)? {
+                    0 => return Ok(None),
+                    4 => {}
+                    _ => return Ok(Some(None)),
+                }
+                let code = match self.encoding {
+                    TEncoding::Utf32Be => u32::from_be_bytes(bytes),
+                    _ => u32::from_le_bytes(bytes),
+                };
+                Ok(Some(char::from_u32(code)))
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.error.is_some() {
+            return None;
+        }
+        let decoded = self.decode();
+        self.position.advance(match decoded {
+            Ok(Some(c)) => c,
+            _ => None,
+        });
+        let error = match decoded {
+            Ok(Some(Some(c))) => return Some(c),
+            Ok(None) => return None,
+            Ok(Some(None)) => ScanError::new(
+                self.position.marker(),
+                &format!("while decoding the stream, found invalid {}", self.encoding),
+            )
+            .with_kind(ErrorKind::InvalidEncoding),
+            Err(e) => read_error(self.position.marker(), &e),
+        };
+        self.error = Some(error);
+        None
+    }
+}
+
+pub(crate) fn read_error(mark: Marker, e: &io::Error) -> ScanError {
+    ScanError::new(mark, &format!("while reading the stream, {}", e)).with_kind(ErrorKind::Io)
+}
+
+// encoding of a stream starting with `bytes`, and the length of its byte
+// order mark
+fn detect_encoding(bytes: &[u8]) -> (TEncoding, usize) {
+    match *bytes {
+        [0x00, 0x00, 0xfe, 0xff, ..] => (TEncoding::Utf32Be, 4),
+        [0x00, 0x00, 0x00, _, ..] => (TEncoding::Utf32Be, 0),
+        [0xff, 0xfe, 0x00, 0x00, ..] => (TEncoding::Utf32Le, 4),
+        [_, 0x00, 0x00, 0x00, ..] => (TEncoding::Utf32Le, 0),
+        [0xfe, 0xff, ..] => (TEncoding::Utf16Be, 2),
+        [0x00, _, ..] => (TEncoding::Utf16Be, 0),
+        [0xff, 0xfe, ..] => (TEncoding::Utf16Le, 2),
+        [_, 0x00, ..] => (TEncoding::Utf16Le, 0),
+        [0xef, 0xbb, 0xbf, ..] => (TEncoding::Utf8, 3),
+        _ => (TEncoding::Utf8, 0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Decoder;
+    use super::ErrorKind;
+    use super::TEncoding;
+
+    fn encode(s: &str, encoding: TEncoding, bom: bool) -> Vec<u8> {
+        let s = if bom {
+            format!("\u{feff}{}", s)
+        } else {
+            s.to_owned()
+        };
+        match encoding {
+            TEncoding::Utf8 => s.into_bytes(),
+            TEncoding::Utf16Le => s.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            TEncoding::Utf16Be => s.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            TEncoding::Utf32Le => s.chars().flat_map(|c| u32::from(c).to_le_bytes()).collect(),
+            TEncoding::Utf32Be => s.chars().flat_map(|c| u32::from(c).to_be_bytes()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        let s = "a: é\n- 😀\n";
+        for &encoding in &[
+            TEncoding::Utf8,
+            TEncoding::Utf16Le,
+            TEncoding::Utf16Be,
+            TEncoding::Utf32Le,
+            TEncoding::Utf32Be,
+        ] {
+            for &bom in &[false, true] {
+                let bytes = encode(s, encoding, bom);
+                let mut decoder = Decoder::new(&bytes[..]).unwrap();
+                assert_eq!(decoder.encoding(), encoding);
+                assert_eq!(decoder.by_ref().collect::<String>(), s);
+                assert!(decoder.error().is_none());
+            }
+        }
+        let mut decoder = Decoder::new(&b""[..]).unwrap();
+        assert_eq!(decoder.encoding(), TEncoding::Utf8);
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn test_invalid_encoding() {
+        for &(bytes, decoded, info) in &[
+            (&b"a:\n \xff"[..], "a:\n ", "invalid UTF-8"),
+            (b"a: \xc3", "a: ", "invalid UTF-8"),
+            (b"\xff\xfea\x00\x00\xd8", "a", "invalid UTF-16LE"),
+        ] {
+            let mut decoder = Decoder::new(bytes).unwrap();
+            assert_eq!(decoder.by_ref().collect::<String>(), decoded);
+            let error = decoder.error().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidEncoding);
+            assert_eq!(
+                error.info(),
+                format!("while decoding the stream, found {}", info)
+            );
+            assert_eq!(error.marker().index, decoded.len());
+        }
+        assert_eq!(Decoder::new(&b"a:\n \xff"[..]).unwrap().last(), Some(' '));
+
+        // CRLF line breaks count as one
+        let mut decoder = Decoder::new(&b"a:\r\n \xff"[..]).unwrap();
+        assert_eq!(decoder.by_ref().count(), 5);
+        let mark = decoder.error().unwrap().marker();
+        assert_eq!((mark.line, mark.col), (2, 1));
+    }
+}
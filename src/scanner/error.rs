@@ -1,27 +1,142 @@
+use std::cmp;
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result;
 
 use super::Marker;
+use super::Span;
+
+/// What went wrong, to tell errors apart without matching their message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The stream ends in the middle of a construct, e.g. an unclosed `[`.
+    UnexpectedEof,
+    /// The stream could not be read.
+    Io,
+    /// The stream is not valid in the encoding it was detected to use.
+    InvalidEncoding,
+    /// Content is not indented as its context requires.
+    BadIndentation,
+    /// A tab is used where only spaces can indent.
+    TabIndentation,
+    /// The source is not valid YAML, e.g. `a: b: c`.
+    Syntax,
+    /// An escape sequence of a double-quoted scalar or a tag is invalid.
+    InvalidEscape,
+    /// A `%YAML` or `%TAG` directive is invalid or repeated.
+    InvalidDirective,
+    /// A tag is malformed or uses an undefined handle.
+    InvalidTag,
+    /// An alias refers to an anchor that is not defined before it.
+    UnknownAnchor,
+    /// A key appears twice in a mapping.
+    DuplicateKey,
+    /// A merge key `<<` is not given a mapping or a sequence of mappings.
+    InvalidMerge,
+    /// A limit set on the parser or the loader is exceeded, e.g. the nesting
+    /// depth.
+    LimitExceeded,
+    /// An error raised outside of this crate, e.g. by a constructor or a
+    /// `TryEventReceiver`.
+    Custom,
+}
 
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct ScanError {
     mark: Marker,
+    // end of the offending text, `mark` if unknown
+    end: Marker,
+    kind: ErrorKind,
     info: String,
+    // boxed to keep results small, as it is seldom set
+    context: Option<Box<(Marker, String)>>,
 }
 
 impl ScanError {
+    /// Error of kind `ErrorKind::Custom`, see `with_kind`.
     pub fn new(mark: Marker, info: &str) -> ScanError {
         ScanError {
             mark,
+            end: mark,
+            kind: ErrorKind::Custom,
             info: info.to_owned(),
+            context: None,
         }
     }
 
+    /// Point at a second location explaining the error, e.g. the first
+    /// occurrence of a duplicate key.
+    pub fn with_context(mut self, mark: Marker, context: &str) -> ScanError {
+        self.context = Some(Box::new((mark, context.to_owned())));
+        self
+    }
+
+    pub fn with_kind(mut self, kind: ErrorKind) -> ScanError {
+        self.kind = kind;
+        self
+    }
+
+    /// Point at the whole offending text, e.g. a token, instead of its start
+    /// only.
+    pub fn with_span(mut self, span: Span) -> ScanError {
+        self.mark = span.start;
+        self.end = span.end;
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The message describing the error, without its position.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
     pub fn marker(&self) -> &Marker {
         &self.mark
     }
+
+    /// The offending text, empty if only its start is known.
+    pub fn span(&self) -> Span {
+        Span::new(self.mark, self.end)
+    }
+
+    pub fn context_marker(&self) -> Option<&Marker> {
+        self.context.as_ref().map(|context| &context.0)
+    }
+
+    /// Display the error along with the lines of `source` it points at,
+    /// `source` being the text the error was found in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let source = "a:\n  b: [1, 2\n";
+    /// let err = YamlLoader::load_from_str(source).unwrap_err();
+    /// assert_eq!(
+    ///     err.diagnostic(source).to_string(),
+    ///     "while parsing a flow sequence, expected ',' or ']' at line 3 column 1
+    ///   |
+    /// 3 |\x20
+    ///   | ^
+    ///   = while parsing a flow sequence started at line 2 column 6
+    ///   |
+    /// 2 |   b: [1, 2
+    ///   |      ^
+    /// "
+    /// );
+    /// ```
+    pub fn diagnostic<'a>(&'a self, source: &'a str) -> Diagnostic<'a> {
+        Diagnostic {
+            error: self,
+            source,
+        }
+    }
 }
 
 impl Error for ScanError {
@@ -42,6 +157,175 @@ impl Display for ScanError {
             self.info,
             self.mark.line,
             self.mark.col + 1 // col starts from 0
-        )
+        )?;
+        if let Some((ref mark, ref context)) = self.context.as_deref() {
+            write!(
+                formatter,
+                ", {} at line {} column {}",
+                context,
+                mark.line,
+                mark.col + 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A `ScanError` displayed with the lines of the source it points at, see
+/// `ScanError::diagnostic`.
+#[derive(Debug)]
+pub struct Diagnostic<'a> {
+    error: &'a ScanError,
+    source: &'a str,
+}
+
+impl<'a> Diagnostic<'a> {
+    // print the lines from `start` to `end`, underlining the text between
+    // them, or the character at `start` if they are equal
+    fn snippet(&self, f: &mut Formatter, start: Marker, end: Marker, width: usize) -> Result {
+        let source = self.source;
+        let start_offset = start.byte_offset;
+        if start_offset > source.len() || !source.is_char_boundary(start_offset) {
+            // not the source of the error
+            return Ok(());
+        }
+        let end_offset = match end.byte_offset {
+            offset if offset > source.len() || !source.is_char_boundary(offset) => start_offset,
+            offset => cmp::max(offset, start_offset),
+        };
+        writeln!(f, "{:w$} |", "", w = width)?;
+        let mut line_no = source[..start_offset].matches('\n').count() + 1;
+        if start_offset == source.len() && start.line > line_no {
+            // the stream end of a source without a final line break is on a
+            // line of its own
+            writeln!(f, "{:>w$} | ", start.line, w = width)?;
+            return writeln!(f, "{:w$} | ^", "", w = width);
+        }
+        let mut line_start = source[..start_offset].rfind('\n').map_or(0, |i| i + 1);
+        loop {
+            let line_end = source[line_start..]
+                .find('\n')
+                .map_or(source.len(), |i| line_start + i);
+            let line = source[line_start..line_end].trim_end_matches('\r');
+            writeln!(f, "{:>w$} | {}", line_no, line, w = width)?;
+
+            let from = cmp::max(start_offset, line_start) - line_start;
+            let to = cmp::min(end_offset, line_start + line.len()).saturating_sub(line_start);
+            let marked = if line_start <= start_offset {
+                cmp::max(line[from..cmp::max(from, to)].chars().count(), 1)
+            } else {
+                line[..to].chars().count()
+            };
+            // keep tabs so that the underline lines up with the text
+            let indent: String = line[..from]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            writeln!(f, "{:w$} | {}{}", "", indent, "^".repeat(marked), w = width)?;
+
+            if end_offset <= line_end + 1 || line_end == source.len() {
+                return Ok(());
+            }
+            line_start = line_end + 1;
+            line_no += 1;
+        }
+    }
+}
+
+impl<'a> Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let error = self.error;
+        writeln!(
+            f,
+            "{} at line {} column {}",
+            error.info,
+            error.mark.line,
+            error.mark.col + 1
+        )?;
+        let last_line = match error.context.as_deref() {
+            Some((mark, _)) => cmp::max(error.end.line, mark.line),
+            None => error.end.line,
+        };
+        let width = last_line.to_string().len();
+        self.snippet(f, error.mark, error.end, width)?;
+        if let Some(&(mark, ref context)) = error.context.as_deref() {
+            writeln!(
+                f,
+                "{:w$} = {} at line {} column {}",
+                "",
+                context,
+                mark.line,
+                mark.col + 1,
+                w = width
+            )?;
+            self.snippet(f, mark, mark, width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Marker;
+    use super::ScanError;
+    use crate::yaml::YamlLoader;
+
+    #[test]
+    fn test_diagnostic() {
+        let source = "[a,\n\t{x: y} \"q\n r\"]\n";
+        let err = YamlLoader::load_from_str(source).unwrap_err();
+        assert_eq!(
+            err.diagnostic(source).to_string(),
+            "while parsing a flow sequence, expected ',' or ']' at line 2 column 9
+  |
+2 | \t{x: y} \"q
+  | \t       ^^
+3 |  r\"]
+  | ^^^
+  = while parsing a flow sequence started at line 1 column 1
+  |
+1 | [a,
+  | ^
+"
+        );
+
+        // CRLF line breaks count as one
+        let source = "a: 1\r\nb: [1\r\n";
+        let err = YamlLoader::load_from_str(source).unwrap_err();
+        assert_eq!(
+            err.diagnostic(source).to_string(),
+            "while parsing a flow sequence, expected ',' or ']' at line 3 column 1
+  |
+3 | 
+  | ^
+  = while parsing a flow sequence started at line 2 column 4
+  |
+2 | b: [1
+  |    ^
+"
+        );
+
+        // no line break at the end of the source
+        let source = "a: [b";
+        let err = YamlLoader::load_from_str(source).unwrap_err();
+        assert_eq!(
+            err.diagnostic(source).to_string(),
+            "while parsing a flow sequence, expected ',' or ']' at line 2 column 1
+  |
+2 | 
+  | ^
+  = while parsing a flow sequence started at line 1 column 4
+  |
+1 | a: [b
+  |    ^
+"
+        );
+
+        // the source does not match the error
+        let err = ScanError::new(Marker::new(10, 2, 0).with_byte_offset(10), "oops");
+        assert_eq!(
+            err.diagnostic("a: b").to_string(),
+            "oops at line 2 column 1\n"
+        );
     }
 }
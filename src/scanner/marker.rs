@@ -1,12 +1,107 @@
+/// Position in the source.
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub struct Marker {
+    /// Number of chars before the position.
     pub index: usize,
+    /// Line of the position, starting from 1.
     pub line: usize,
+    /// Number of chars between the start of the line and the position.
     pub col: usize,
+    /// Number of UTF-8 bytes before the position, which can be used to
+    /// slice the source `&str`. For chars read from a `Decoder`, it counts
+    /// the bytes of the decoded text, byte order mark excluded, rather than
+    /// the bytes read.
+    pub byte_offset: usize,
 }
 
 impl Marker {
     pub fn new(index: usize, line: usize, col: usize) -> Marker {
-        Marker { index, line, col }
+        Marker {
+            index,
+            line,
+            col,
+            byte_offset: 0,
+        }
+    }
+
+    /// Set the number of UTF-8 bytes before the position.
+    pub fn with_byte_offset(mut self, byte_offset: usize) -> Marker {
+        self.byte_offset = byte_offset;
+        self
+    }
+}
+
+/// Position of the next char of a stream, counted as the `Scanner` does, for
+/// the readers of its chars.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Position {
+    mark: Marker,
+    // the last char is a '\r', counted once the next one tells whether it
+    // starts a CRLF line break
+    cr: bool,
+}
+
+impl Position {
+    pub(crate) fn new() -> Position {
+        Position {
+            mark: Marker::new(0, 1, 0),
+            cr: false,
+        }
+    }
+
+    /// Account for the next char of the stream, `None` where it ends.
+    pub(crate) fn advance(&mut self, c: Option<char>) {
+        if self.cr {
+            self.cr = false;
+            if c == Some('\n') {
+                self.mark.col += 1;
+            } else {
+                self.mark.line += 1;
+                self.mark.col = 0;
+            }
+        }
+        if let Some(c) = c {
+            self.mark.index += 1;
+            self.mark.byte_offset += c.len_utf8();
+            match c {
+                '\r' => self.cr = true,
+                '\n' => {
+                    self.mark.line += 1;
+                    self.mark.col = 0;
+                }
+                _ => self.mark.col += 1,
+            }
+        }
+    }
+
+    pub(crate) fn marker(&self) -> Marker {
+        self.mark
+    }
+}
+
+/// Source range of a token or an event, from `start` included to `end`
+/// excluded.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct Span {
+    pub start: Marker,
+    pub end: Marker,
+}
+
+impl Span {
+    pub fn new(start: Marker, end: Marker) -> Span {
+        Span { start, end }
+    }
+
+    /// Span of a construct that takes no room in the source, such as an
+    /// implicit block collection start or an empty scalar.
+    pub fn empty(mark: Marker) -> Span {
+        Span {
+            start: mark,
+            end: mark,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
     }
 }
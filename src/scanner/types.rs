@@ -1,8 +1,26 @@
 use super::Marker;
+use super::Span;
+use std::fmt;
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum TEncoding {
     Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl fmt::Display for TEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TEncoding::Utf8 => "UTF-8",
+            TEncoding::Utf16Le => "UTF-16LE",
+            TEncoding::Utf16Be => "UTF-16BE",
+            TEncoding::Utf32Le => "UTF-32LE",
+            TEncoding::Utf32Be => "UTF-32BE",
+        })
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
@@ -44,8 +62,10 @@ pub enum TokenType {
     Comment(String, bool),
 }
 
+/// A token along with the source range it spans. Tokens inserted by the
+/// scanner, such as block collection starts, take no room.
 #[derive(Clone, PartialEq, Debug, Eq)]
-pub struct Token(pub Marker, pub TokenType);
+pub struct Token(pub Span, pub TokenType);
 
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct SimpleKey {
@@ -1,20 +1,47 @@
+use crate::parser::AnchorID;
 use crate::parser::Event;
-use crate::parser::EventReceiver;
 use crate::parser::Parser;
+use crate::parser::Tag;
+use crate::parser::TryEventReceiver;
+use crate::scanner::read_error;
+use crate::scanner::Decoder;
 use crate::scanner::Marker;
 use crate::scanner::ScanError;
+use crate::scanner::Span;
 use crate::scanner::TScalarStyle;
-use crate::scanner::TokenType;
 use linked_hash_map::LinkedHashMap;
 use std::collections::BTreeMap;
 use std::collections::LinkedList;
 use std::f64;
 use std::i64;
-use std::mem;
+use std::io;
 use std::ops::Index;
 use std::string;
 use std::vec;
 
+pub use self::constructors::Constructor;
+use self::limits::yaml_nodes;
+use self::limits::BoundedSource;
+use self::limits::BudgetedLoader;
+use self::limits::DocumentBudget;
+use self::mapping::MappingState;
+pub use self::marked::MarkedHash;
+pub use self::marked::MarkedNode;
+pub use self::marked::MarkedYaml;
+pub use self::marked::MarkedYamlLoader;
+pub use self::options::DuplicateKeys;
+pub use self::options::LoaderOptions;
+use self::schema::parse_float_1_1;
+use self::schema::parse_int_1_1;
+pub use self::schema::Schema;
+
+mod constructors;
+mod limits;
+mod mapping;
+mod marked;
+mod options;
+mod schema;
+
 /// A YAML node is stored as this `Yaml` enumeration, which provides an easy way
 /// to access your YAML document.
 ///
@@ -49,8 +76,20 @@ pub enum Yaml {
     ///
     /// Insertion order will match the order of insertion into the map.
     Hash(self::Hash),
-    /// Alias, not fully supported yet.
-    Alias(usize),
+    /// Node carrying an anchor, e.g. `&name value`. Only produced when loading
+    /// with `LoaderOptions::anchors`, otherwise anchors are dropped.
+    Anchor(string::String, Box<Yaml>),
+    /// Alias to the node anchored with the given name, e.g. `*name`. Only
+    /// produced when loading with `LoaderOptions::anchors`, otherwise aliases
+    /// are resolved to a copy of the anchored node.
+    Alias(string::String),
+    /// Node carrying a tag the loader does not resolve by itself, e.g.
+    /// `!Ref name`. The tag is stored in shorthand form using the default tag
+    /// handles when possible, e.g. `!Ref`, `!!set` or
+    /// `!<tag:example.com,2000:app>`. Only
+    /// produced when loading with `LoaderOptions::tags`, otherwise such tags
+    /// are dropped.
+    Tagged(string::String, Box<Yaml>),
     /// Comment, Inline
     Comment(string::String, bool),
     /// Meta operation (set emitter formatting options)
@@ -85,10 +124,24 @@ pub enum StringFormat {
     Block,
 }
 
+/// Style of the collections written by the `YamlEmitter`.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub enum CollectionFormat {
+    /// Entries on lines of their own, e.g. `- a`.
+    Block,
+    /// Entries on one line between brackets or braces, e.g. `[a, b]`. Used
+    /// for the collections nested in a flow collection too.
+    Flow,
+    /// Flow style for collections of scalars fitting on their line, see
+    /// `YamlEmitter::line_width`, block style otherwise.
+    Auto,
+}
+
 #[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
 pub enum Meta {
     Integer(IntegerFormat, Box<Yaml>),
     String(StringFormat, Box<Yaml>),
+    Collection(CollectionFormat, Box<Yaml>),
 }
 
 // parse f64 as Core schema
@@ -105,20 +158,55 @@ fn parse_f64(v: &str) -> Option<f64> {
 pub struct YamlLoader {
     docs: Vec<Yaml>,
     // states
-    // (current node, anchor_id) tuple
-    doc_stack: Vec<(Yaml, usize)>,
-    key_stack: Vec<Yaml>,
-    anchor_map: BTreeMap<usize, Yaml>,
+    // (current node, anchor_id, start marker, tag) tuple
+    doc_stack: Vec<(Yaml, usize, Marker, Option<Tag>)>,
+    mappings: Vec<MappingState<Yaml>>,
+    // anchored node and its number of nodes
+    anchor_map: BTreeMap<usize, (Yaml, usize)>,
+    anchor_names: BTreeMap<usize, String>,
     comments: LinkedList<Yaml>,
+    options: LoaderOptions,
+    // version of the current document, from its %YAML directive
+    version: Option<(u32, u32)>,
+    budget: DocumentBudget,
+    error: Option<ScanError>,
+}
+
+impl TryEventReceiver for YamlLoader {
+    fn on_event(&mut self, ev: Event, span: Span) -> Result<(), ScanError> {
+        let anchor_map = &self.anchor_map;
+        let anchors = self.options.anchors;
+        self.budget.on_event(&self.options, &ev, span.start, |id| {
+            match anchor_map.get(&id) {
+                Some((_, nodes)) if !anchors => *nodes,
+                _ => 1,
+            }
+        })?;
+        self.load_event(ev, span.start);
+        // errors of the loader stop the parser
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn on_anchor(&mut self, id: AnchorID, name: &str) {
+        if self.options.anchors {
+            self.anchor_names.insert(id, name.to_owned());
+        }
+    }
 }
 
-impl EventReceiver for YamlLoader {
-    fn on_event(&mut self, ev: Event, _marker: Marker) {
+impl YamlLoader {
+    fn load_event(&mut self, ev: Event, marker: Marker) {
         #[cfg(test)]
-        println!("EV {:?} @ {:?}", ev, _marker);
+        println!("EV {:?} @ {:?}", ev, marker);
         match ev {
-            Event::DocumentStart => {
-                // do nothing
+            Event::DocumentStart(version) => {
+                self.version = version;
+                // anchors are scoped to their document
+                self.anchor_map.clear();
+                self.anchor_names.clear();
             }
             Event::DocumentEnd => {
                 match self.doc_stack.len() {
@@ -128,72 +216,74 @@ impl EventReceiver for YamlLoader {
                     _ => unreachable!(),
                 }
             }
-            Event::SequenceStart(aid) => {
-                self.doc_stack.push((Yaml::Array(Vec::new()), aid));
-                self.dump_comments();
+            Event::SequenceStart(aid, tag) => {
+                self.doc_stack
+                    .push((Yaml::Array(Vec::new()), aid, marker, tag));
+                self.dump_comments(marker);
             }
             Event::SequenceEnd => {
-                self.dump_comments();
-                let node = self.doc_stack.pop().unwrap();
-                self.insert_new_node(node);
+                self.dump_comments(marker);
+                let (node, aid, mark, tag) = self.doc_stack.pop().unwrap();
+                if let Some(node) = self.apply_tag(node, tag, mark) {
+                    self.insert_new_node((node, aid), mark);
+                }
             }
-            Event::MappingStart(aid) => {
-                if let Some((Yaml::Array(_), _)) = self.doc_stack.last() {
-                    self.dump_comments();
+            Event::MappingStart(aid, tag) => {
+                if let Some((Yaml::Array(_), _, _, _)) = self.doc_stack.last() {
+                    self.dump_comments(marker);
                 }
-                self.doc_stack.push((Yaml::Hash(Hash::new()), aid));
-                self.key_stack.push(Yaml::BadValue);
+                self.doc_stack
+                    .push((Yaml::Hash(Hash::new()), aid, marker, tag));
+                self.mappings.push(MappingState::default());
             }
             Event::MappingEnd => {
-                self.dump_comments();
-                self.key_stack.pop().unwrap();
-                let node = self.doc_stack.pop().unwrap();
-                self.insert_new_node(node);
+                self.dump_comments(marker);
+                let (mut node, aid, mark, tag) = self.doc_stack.pop().unwrap();
+                if let Yaml::Hash(ref mut h) = node {
+                    self.mappings.pop().unwrap().finish(h);
+                }
+                if let Some(node) = self.apply_tag(node, tag, mark) {
+                    self.insert_new_node((node, aid), mark);
+                }
             }
             Event::Scalar(v, style, aid, tag) => {
-                let node = if style != TScalarStyle::Plain {
+                if self.resolves_merge_keys()
+                    && style == TScalarStyle::Plain
+                    && tag.is_none()
+                    && v == "<<"
+                    && self.is_key_expected()
+                {
+                    self.dump_comments(marker);
+                    self.mappings.last_mut().unwrap().expect_merge();
+                    return;
+                }
+                let node = if style != TScalarStyle::Plain || tag.is_some() {
                     Yaml::String(v)
-                } else if let Some(TokenType::Tag(ref handle, ref suffix)) = tag {
-                    // XXX tag:yaml.org,2002:
-                    if handle == "!!" {
-                        match suffix.as_ref() {
-                            "bool" => {
-                                // "true" or "false"
-                                match v.parse::<bool>() {
-                                    Err(_) => Yaml::BadValue,
-                                    Ok(v) => Yaml::Boolean(v),
-                                }
-                            }
-                            "int" => match v.parse::<i64>() {
-                                Err(_) => Yaml::BadValue,
-                                Ok(v) => Yaml::Integer(v),
-                            },
-                            "float" => match parse_f64(&v) {
-                                Some(_) => Yaml::Real(v),
-                                None => Yaml::BadValue,
-                            },
-                            "null" => match v.as_ref() {
-                                "~" | "null" => Yaml::Null,
-                                _ => Yaml::BadValue,
-                            },
-                            _ => Yaml::String(v),
-                        }
-                    } else {
-                        Yaml::String(v)
-                    }
+                } else if self.options.merge_keys && v == "<<" && self.is_key_expected() {
+                    // merge key left as is, tagged so that it is told apart
+                    // from a string key
+                    Yaml::Tagged("!!merge".to_owned(), Box::new(Yaml::String(v)))
                 } else {
-                    // Datatype is not specified, or unrecognized
-                    Yaml::from_str(&v)
+                    // Datatype is not specified
+                    self.options.schema.for_version(self.version).resolve(&v)
+                };
+                let node = match self.apply_tag(node, tag, marker) {
+                    Some(node) => node,
+                    None => return,
                 };
-                self.dump_comments();
-                self.insert_new_node((node, aid));
+                self.dump_comments(marker);
+                self.insert_new_node((node, aid), marker);
             }
             Event::Alias(id) => {
-                let n = match self.anchor_map.get(&id) {
-                    Some(v) => v.clone(),
-                    None => Yaml::BadValue,
+                let n = if self.options.anchors {
+                    Yaml::Alias(self.anchor_names[&id].clone())
+                } else {
+                    match self.anchor_map.get(&id) {
+                        Some((v, _)) => v.clone(),
+                        None => Yaml::BadValue,
+                    }
                 };
-                self.insert_new_node((n, 0));
+                self.insert_new_node((n, 0), marker);
             }
             Event::Comment(comment, inline) => {
                 let node = Yaml::Comment(comment, inline);
@@ -203,18 +293,22 @@ impl EventReceiver for YamlLoader {
         }
         // println!("DOC {:?}", self.doc_stack);
     }
-}
 
-impl YamlLoader {
-    fn insert_new_node(&mut self, node: (Yaml, usize)) {
+    fn insert_new_node(&mut self, mut node: (Yaml, usize), mark: Marker) {
         // valid anchor id starts from 1
         if node.1 > 0 {
-            self.anchor_map.insert(node.1, node.0.clone());
+            if self.options.anchors {
+                let name = self.anchor_names[&node.1].clone();
+                node.0 = Yaml::Anchor(name, Box::new(node.0));
+            } else {
+                let nodes = yaml_nodes(&node.0);
+                self.anchor_map.insert(node.1, (node.0.clone(), nodes));
+            }
         }
         if self.doc_stack.is_empty() {
-            self.doc_stack.push(node);
+            self.doc_stack.push((node.0, node.1, mark, None));
         } else {
-            let (parent, _) = self.doc_stack.last_mut().unwrap();
+            let (parent, _, _, _) = self.doc_stack.last_mut().unwrap();
             match *parent {
                 Yaml::Array(ref mut v) => v.push(node.0),
                 Yaml::Hash(ref mut h) => {
@@ -222,15 +316,10 @@ impl YamlLoader {
                         h.insert(node.0, Yaml::BadValue);
                         return;
                     }
-                    let cur_key = self.key_stack.last_mut().unwrap();
-                    // current node is a key
-                    if cur_key.is_badvalue() {
-                        *cur_key = node.0;
-                    // current node is a value
-                    } else {
-                        let mut newkey = Yaml::BadValue;
-                        mem::swap(&mut newkey, cur_key);
-                        h.insert(newkey, node.0);
+                    let mapping = self.mappings.last_mut().unwrap();
+                    let duplicate_keys = self.options.duplicate_keys;
+                    if let Err(e) = mapping.insert(h, node.0, mark, duplicate_keys) {
+                        self.error = Some(e);
                     }
                 }
                 _ => {
@@ -243,31 +332,220 @@ impl YamlLoader {
         }
     }
 
-    fn dump_comments(&mut self) {
+    fn dump_comments(&mut self, mark: Marker) {
         // Only dumps the coments if there is at least one document
         if self.doc_stack.is_empty() {
             return;
         }
         while let Some(comment) = self.comments.pop_back() {
-            self.insert_new_node((comment, 0));
+            self.insert_new_node((comment, 0), mark);
+        }
+    }
+
+    /// Build the value of a tagged node with the constructor of its tag, see
+    /// `LoaderOptions::constructor_for`. Without a constructor, the node is
+    /// wrapped in a `Yaml::Tagged` if tags are kept. Returns `None` if the
+    /// constructor failed.
+    fn apply_tag(&mut self, node: Yaml, tag: Option<Tag>, mark: Marker) -> Option<Yaml> {
+        let uri = match tag {
+            Some(tag) => tag.uri,
+            None => return Some(node),
+        };
+        let tag = tag_shorthand(&uri);
+        if let Some(constructor) = self.options.constructor_for(&tag, self.version) {
+            match constructor(node) {
+                Ok(node) => Some(node),
+                Err(info) => {
+                    self.error = Some(ScanError::new(mark, &info));
+                    None
+                }
+            }
+        } else if self.options.tags && !is_core_tag(&uri) {
+            Some(Yaml::Tagged(tag, Box::new(node)))
+        } else {
+            Some(node)
+        }
+    }
+
+    fn resolves_merge_keys(&self) -> bool {
+        // aliases are not resolved when anchors are kept, leave merges as is
+        self.options.merge_keys && !self.options.anchors
+    }
+
+    fn is_key_expected(&self) -> bool {
+        match self.doc_stack.last() {
+            Some((Yaml::Hash(_), _, _, _)) => self.mappings.last().unwrap().is_key_expected(),
+            _ => false,
         }
     }
 
     pub fn load_from_str(source: &str) -> Result<Vec<Yaml>, ScanError> {
-        let mut loader = YamlLoader {
+        let options = LoaderOptions::default().comments(cfg!(test));
+        YamlLoader::load_with_options(source, options)
+    }
+
+    /// Loader of documents configured by `options`, to be fed the events of
+    /// a `Parser`, e.g. with `documents`.
+    pub fn new(options: LoaderOptions) -> YamlLoader {
+        YamlLoader {
             docs: Vec::new(),
             doc_stack: Vec::new(),
-            key_stack: Vec::new(),
+            mappings: Vec::new(),
             anchor_map: BTreeMap::new(),
+            anchor_names: BTreeMap::new(),
             comments: LinkedList::new(),
-        };
+            budget: DocumentBudget::new(&options),
+            options,
+            version: None,
+            error: None,
+        }
+    }
 
-        let with_comments = cfg!(test);
-        let mut parser = Parser::new(source.chars(), &mut loader, with_comments);
+    /// Load the documents of `source` as configured by `options`.
+    pub fn load_with_options(source: &str, options: LoaderOptions) -> Result<Vec<Yaml>, ScanError> {
+        let mut loader = YamlLoader::new(options);
+        loader.load_source(source.chars())?;
+        Ok(loader.docs)
+    }
 
-        parser.load(true)?;
+    /// Load the documents read from `reader` as configured by `options`,
+    /// decoding them from any of the encodings YAML allows, see `Decoder`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::{LoaderOptions, YamlLoader};
+    ///
+    /// let file: &[u8] = b"\xef\xbb\xbfa: [1, 2]\n";
+    /// let docs = YamlLoader::load_from_reader(file, LoaderOptions::new()).unwrap();
+    /// assert_eq!(docs[0]["a"][1].as_i64(), Some(2));
+    /// ```
+    pub fn load_from_reader<R: io::Read>(
+        reader: R,
+        options: LoaderOptions,
+    ) -> Result<Vec<Yaml>, ScanError> {
+        let mut decoder = Decoder::new(reader).map_err(|e| read_error(Marker::new(0, 1, 0), &e))?;
+        let mut loader = YamlLoader::new(options);
+        let result = loader.load_source(&mut decoder);
+
+        // the parser sees the stream end where the decoder stops too
+        if let Some(e) = decoder.error() {
+            return Err(e.clone());
+        }
+        result?;
         Ok(loader.docs)
     }
+
+    /// Load the documents of `source` as configured by `options`, see
+    /// `load_from_reader`.
+    pub fn load_from_slice(source: &[u8], options: LoaderOptions) -> Result<Vec<Yaml>, ScanError> {
+        YamlLoader::load_from_reader(source, options)
+    }
+
+    /// Iterate over the documents of `source`, loading them one at a time so
+    /// that only the document being loaded is held in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::{LoaderOptions, YamlLoader};
+    ///
+    /// let source = "a: 1\n---\nb: 2\n--- [\n";
+    /// let mut loader = YamlLoader::new(LoaderOptions::new());
+    /// let mut docs = loader.documents(source.chars());
+    /// assert_eq!(docs.next().unwrap().unwrap()["a"].as_i64(), Some(1));
+    /// assert_eq!(docs.next().unwrap().unwrap()["b"].as_i64(), Some(2));
+    /// assert!(docs.next().unwrap().is_err());
+    /// assert!(docs.next().is_none());
+    /// ```
+    pub fn documents<T: Iterator<Item = char>>(&mut self, source: T) -> Documents<'_, T> {
+        Documents {
+            parser: self.bounded_parser(source),
+            finished: false,
+        }
+    }
+}
+
+impl BudgetedLoader for YamlLoader {
+    fn options(&self) -> &LoaderOptions {
+        &self.options
+    }
+
+    fn budget(&self) -> &DocumentBudget {
+        &self.budget
+    }
+
+    fn reset(&mut self) {
+        self.docs.clear();
+        self.doc_stack.clear();
+        self.mappings.clear();
+        self.anchor_map.clear();
+        self.anchor_names.clear();
+        self.comments.clear();
+        self.version = None;
+        self.budget = DocumentBudget::new(&self.options);
+        self.error = None;
+    }
+}
+
+/// Iterator over the documents of a stream, see `YamlLoader::documents`.
+///
+/// Yields the documents in order, up to the end of the stream or the first
+/// error.
+pub struct Documents<'a, T> {
+    parser: Parser<'a, BoundedSource<T>, YamlLoader>,
+    finished: bool,
+}
+
+impl<'a, T: Iterator<Item = char>> Iterator for Documents<'a, T> {
+    type Item = Result<Yaml, ScanError>;
+
+    fn next(&mut self) -> Option<Result<Yaml, ScanError>> {
+        if self.finished {
+            return None;
+        }
+        let result = self.parser.load(false);
+        let loader = self.parser.receiver();
+        // the parser sees the stream end where the source is cut
+        let result = loader
+            .budget
+            .check_source(result)
+            .map(|()| loader.docs.pop());
+        match result {
+            Ok(Some(doc)) => {
+                self.finished = !loader.options.multi_document;
+                Some(Ok(doc))
+            }
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                loader.reset();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Tags resolved by the loader itself without a constructor: the core schema
+/// collections and the non-specific `!` tag.
+fn is_core_tag(uri: &str) -> bool {
+    matches!(uri, "tag:yaml.org,2002:seq" | "tag:yaml.org,2002:map" | "!")
+}
+
+/// Format a resolved tag using the default tag handles when possible, e.g.
+/// `!!set`, `!Ref` or `!<tag:example.com,2000:app>`, so that it can be written
+/// in a document without `%TAG` directives.
+pub(crate) fn tag_shorthand(uri: &str) -> String {
+    if let Some(suffix) = uri.strip_prefix("tag:yaml.org,2002:") {
+        format!("!!{}", suffix)
+    } else if uri.starts_with('!') {
+        uri.to_owned()
+    } else {
+        format!("!<{}>", uri)
+    }
 }
 
 macro_rules! define_as (
@@ -393,6 +671,25 @@ impl Yaml {
             _ => Yaml::String(v.to_owned()),
         }
     }
+
+    /// Like `from_str`, but resolves plain scalars following YAML 1.1, where
+    /// e.g. `yes` and `off` are booleans, `0777` is an octal integer, and
+    /// numbers may be written `0b1010`, `1_000_000` or `1:30`.
+    pub fn from_str_1_1(v: &str) -> Yaml {
+        match v {
+            "" | "~" | "null" | "Null" | "NULL" => Yaml::Null,
+            "y" | "Y" | "yes" | "Yes" | "YES" | "true" | "True" | "TRUE" | "on" | "On" | "ON" => {
+                Yaml::Boolean(true)
+            }
+            "n" | "N" | "no" | "No" | "NO" | "false" | "False" | "FALSE" | "off" | "Off"
+            | "OFF" => Yaml::Boolean(false),
+            _ => match (parse_int_1_1(v), parse_float_1_1(v)) {
+                (Some(i), _) => Yaml::Integer(i),
+                (None, Some(f)) => Yaml::Real(f),
+                (None, None) => Yaml::String(v.to_owned()),
+            },
+        }
+    }
 }
 
 static BAD_VALUE: Yaml = Yaml::BadValue;
@@ -448,6 +745,7 @@ impl Iterator for YamlIter {
 
 #[cfg(test)]
 mod test {
+    use crate::scanner::ErrorKind;
     use crate::yaml::*;
     use std::f64;
     #[test]
@@ -506,10 +804,45 @@ a7: 你好
 ---
 'a scalar'
 ";
-        let out = YamlLoader::load_from_str(&s).unwrap();
+        let out = YamlLoader::load_from_str(s).unwrap();
         assert_eq!(out.len(), 3);
     }
 
+    #[test]
+    fn test_load_with_options_comments() {
+        let s = "
+# leading comment
+a: 1 # inline comment
+";
+        let without = LoaderOptions::new().comments(false);
+        let out = YamlLoader::load_with_options(s, without).unwrap();
+        assert_eq!(out[0].as_hash().unwrap().len(), 1);
+
+        let with = LoaderOptions::new().comments(true);
+        let out = YamlLoader::load_with_options(s, with).unwrap();
+        let keys: Vec<_> = out[0].as_hash().unwrap().keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                Yaml::Comment(" leading comment".to_owned(), false),
+                Yaml::String("a".to_owned()),
+                Yaml::Comment(" inline comment".to_owned(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_with_options_single_document() {
+        let s = "
+'a scalar'
+---
+'a scalar'
+";
+        let options = LoaderOptions::new().multi_document(false);
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        assert_eq!(out.len(), 1);
+    }
+
     #[test]
     fn test_anchor() {
         let s = "
@@ -535,6 +868,625 @@ a1: &DEFAULT
         assert_eq!(doc["a1"]["b2"], Yaml::BadValue);
     }
 
+    #[test]
+    fn test_preserve_anchors() {
+        let s = "
+a1: &DEFAULT
+    b1: 4
+    b2: d
+a2: *DEFAULT
+a3: &num 1
+";
+        let options = LoaderOptions::new().anchors(true);
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        let doc = &out[0];
+        match doc["a1"] {
+            Yaml::Anchor(ref name, ref node) => {
+                assert_eq!(name, "DEFAULT");
+                assert_eq!(node["b1"].as_i64().unwrap(), 4);
+            }
+            ref other => panic!("expected an anchor, got {:?}", other),
+        }
+        assert_eq!(doc["a2"], Yaml::Alias("DEFAULT".to_owned()));
+        assert_eq!(
+            doc["a3"],
+            Yaml::Anchor("num".to_owned(), Box::new(Yaml::Integer(1)))
+        );
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let s = "
+defaults: &defaults
+    image: rust
+    stage: test
+extra: &extra
+    stage: build
+    retry: 2
+job1:
+    <<: *defaults
+    stage: deploy
+job2:
+    <<: [*extra, *defaults]
+    script: make
+job3:
+    \"<<\": *defaults
+";
+        let options = LoaderOptions::new().merge_keys(true);
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        let doc = &out[0];
+
+        let job1 = doc["job1"].as_hash().unwrap();
+        assert_eq!(job1.len(), 2);
+        assert_eq!(doc["job1"]["image"].as_str().unwrap(), "rust");
+        assert_eq!(doc["job1"]["stage"].as_str().unwrap(), "deploy");
+
+        let keys: Vec<_> = doc["job2"].as_hash().unwrap().keys().cloned().collect();
+        assert_eq!(
+            keys,
+            ["stage", "retry", "image", "script"]
+                .iter()
+                .map(|k| Yaml::String(k.to_string()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(doc["job2"]["stage"].as_str().unwrap(), "build");
+
+        // quoted keys are not merge keys
+        assert!(doc["job3"]["<<"].as_hash().is_some());
+
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert!(out[0]["job1"]["<<"].as_hash().is_some());
+    }
+
+    #[test]
+    fn test_repeated_merge_keys() {
+        let s = "
+a: &a {x: 1}
+b: &b {x: 2, y: 2}
+c:
+  <<: *a
+  <<: *b
+  z: 3
+";
+        let merge_key = Yaml::Tagged(
+            "!!merge".to_owned(),
+            Box::new(Yaml::String("<<".to_owned())),
+        );
+        let aliases = Yaml::Array(vec![
+            Yaml::Alias("a".to_owned()),
+            Yaml::Alias("b".to_owned()),
+        ]);
+        for &duplicate_keys in &[
+            DuplicateKeys::Error,
+            DuplicateKeys::LastWins,
+            DuplicateKeys::FirstWins,
+        ] {
+            let options = LoaderOptions::new()
+                .merge_keys(true)
+                .duplicate_keys(duplicate_keys);
+            let out = YamlLoader::load_with_options(s, options.clone()).unwrap();
+            let c = &out[0]["c"];
+            assert_eq!(c.as_hash().unwrap().len(), 3);
+            assert_eq!(c["x"].as_i64(), Some(1));
+            assert_eq!(c["y"].as_i64(), Some(2));
+
+            // left as is, the merge keys are kept as one merging both
+            let out = YamlLoader::load_with_options(s, options.anchors(true)).unwrap();
+            let c = out[0]["c"].as_hash().unwrap();
+            assert_eq!(c.len(), 2);
+            assert_eq!(c[&merge_key], aliases);
+        }
+    }
+
+    #[test]
+    fn test_merge_keys_non_mapping() {
+        let options = LoaderOptions::new().merge_keys(true);
+        let err = YamlLoader::load_with_options("a:\n  <<: 1\n", options.clone()).unwrap_err();
+        assert_eq!(err.marker().line, 2);
+        assert_eq!(err.marker().col, 6);
+
+        let err = YamlLoader::load_with_options("a:\n  <<: [{b: 1}, 2]\n", options);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_tagged() {
+        let s = "
+bucket: !Ref MyBucket
+arn: !GetAtt [MyBucket, Arn]
+config: !include {path: a.yaml}
+set: !!set {a, b}
+verbatim: !<tag:example.com,2000:app> foo
+anchored: &x !env 12
+int: !!int 3
+str: ! 3
+";
+        let options = LoaderOptions::new().tags(true);
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        let doc = &out[0];
+        let tagged = |tag: &str, node: Yaml| Yaml::Tagged(tag.to_owned(), Box::new(node));
+        let string = |s: &str| Yaml::String(s.to_owned());
+
+        assert_eq!(doc["bucket"], tagged("!Ref", string("MyBucket")));
+        assert_eq!(
+            doc["arn"],
+            tagged(
+                "!GetAtt",
+                Yaml::Array(vec![string("MyBucket"), string("Arn")])
+            )
+        );
+        match doc["config"] {
+            Yaml::Tagged(ref tag, ref node) => {
+                assert_eq!(tag, "!include");
+                assert_eq!(node["path"].as_str().unwrap(), "a.yaml");
+            }
+            ref other => panic!("expected a tagged node, got {:?}", other),
+        }
+        match doc["set"] {
+            Yaml::Tagged(ref tag, ref node) => {
+                assert_eq!(tag, "!!set");
+                assert!(node.as_hash().is_some());
+            }
+            ref other => panic!("expected a tagged node, got {:?}", other),
+        }
+        assert_eq!(
+            doc["verbatim"],
+            tagged("!<tag:example.com,2000:app>", string("foo"))
+        );
+        assert_eq!(doc["anchored"], tagged("!env", string("12")));
+        assert_eq!(doc["int"], Yaml::Integer(3));
+        assert_eq!(doc["str"], string("3"));
+
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0]["bucket"], string("MyBucket"));
+        assert!(out[0]["arn"].as_vec().is_some());
+    }
+
+    #[test]
+    fn test_tag_constructors() {
+        let s = "
+home: !env HOME
+timeout: !duration [1, m]
+port: !!int \"8080\"
+";
+        let env: BTreeMap<_, _> = vec![("HOME", "/root")].into_iter().collect();
+        let options = LoaderOptions::new()
+            .constructor("!env", move |node: Yaml| {
+                let name = node.as_str().ok_or("!env expects a scalar")?;
+                match env.get(name) {
+                    Some(v) => Ok(Yaml::String(v.to_string())),
+                    None => Err(format!("undefined variable {}", name)),
+                }
+            })
+            .constructor("!duration", |node: Yaml| match node[1].as_str() {
+                Some("m") => Ok(Yaml::Integer(node[0].as_i64().unwrap() * 60)),
+                _ => Err("unknown unit".to_owned()),
+            });
+        let out = YamlLoader::load_with_options(s, options.clone()).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc["home"].as_str().unwrap(), "/root");
+        assert_eq!(doc["timeout"].as_i64().unwrap(), 60);
+        assert_eq!(doc["port"].as_i64().unwrap(), 8080);
+
+        let err = YamlLoader::load_with_options("a: b\nc: !env USER\n", options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "undefined variable USER at line 2 column 9"
+        );
+    }
+
+    #[test]
+    fn test_version_directive() {
+        let s = "
+%YAML 1.1
+---
+[yes, Off, 0777, -010, 0o17, '0777', 1.5]
+...
+%YAML 1.2
+---
+[yes, Off, 0777, 0o17]
+...
+---
+[yes, 0777]
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(
+            out[0],
+            Yaml::Array(vec![
+                Yaml::Boolean(true),
+                Yaml::Boolean(false),
+                Yaml::Integer(0o777),
+                Yaml::Integer(-0o10),
+                Yaml::String("0o17".to_owned()),
+                Yaml::String("0777".to_owned()),
+                Yaml::Real("1.5".to_owned()),
+            ])
+        );
+        assert_eq!(
+            out[1],
+            Yaml::Array(vec![
+                Yaml::String("yes".to_owned()),
+                Yaml::String("Off".to_owned()),
+                Yaml::Integer(777),
+                Yaml::Integer(0o17),
+            ])
+        );
+        assert_eq!(
+            out[2],
+            Yaml::Array(vec![Yaml::String("yes".to_owned()), Yaml::Integer(777)])
+        );
+
+        // tagged scalars are resolved with the version of their document too
+        let s = "
+%YAML 1.1
+---
+[!!int 0777, !!int 1_000, !!bool yes, !!bool 'Off', !!float 1_000]
+...
+%YAML 1.2
+---
+[!!int 0777, !!int 1_000, !!bool yes]
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(
+            out[0],
+            Yaml::Array(vec![
+                Yaml::Integer(0o777),
+                Yaml::Integer(1000),
+                Yaml::Boolean(true),
+                Yaml::Boolean(false),
+                Yaml::Real("1000".to_owned()),
+            ])
+        );
+        assert_eq!(
+            out[1],
+            Yaml::Array(vec![Yaml::Integer(777), Yaml::BadValue, Yaml::BadValue])
+        );
+        let marked = MarkedYamlLoader::load_from_str(s).unwrap();
+        assert_eq!(Yaml::from(marked[0].clone()), out[0]);
+    }
+
+    #[test]
+    fn test_yaml_1_1_scalars() {
+        let ints = [
+            ("0b1010", 10),
+            ("-0b1_0", -2),
+            ("0777", 0o777),
+            ("+0_17", 0o17),
+            ("1_000_000", 1_000_000),
+            ("0x1F", 31),
+            ("1:30", 90),
+            ("-190:20:30", -685_230),
+            ("0", 0),
+        ];
+        for &(v, i) in &ints {
+            assert_eq!(Yaml::from_str_1_1(v), Yaml::Integer(i), "{}", v);
+        }
+        let reals = [
+            ("1_000.5", "1000.5"),
+            ("-.5", "-.5"),
+            ("6.8523015e+5", "6.8523015e+5"),
+            ("190:20:30.15", "685230.15"),
+            (".NaN", ".nan"),
+            ("+.Inf", ".inf"),
+        ];
+        for &(v, r) in &reals {
+            assert_eq!(Yaml::from_str_1_1(v), Yaml::Real(r.to_owned()), "{}", v);
+        }
+        for &v in &[
+            "08", "0o17", "1e3", "1.0e3", "0:30", "1:60", "_1", ".", "1.2.3",
+        ] {
+            assert_eq!(Yaml::from_str_1_1(v), Yaml::String(v.to_owned()), "{}", v);
+        }
+        assert_eq!(Yaml::from_str_1_1("1:30.5").as_f64(), Some(90.5));
+        assert_eq!(Yaml::from_str_1_1("Off"), Yaml::Boolean(false));
+        assert_eq!(Yaml::from_str_1_1("NULL"), Yaml::Null);
+    }
+
+    #[test]
+    fn test_schema_option() {
+        let s = "
+a: yes
+b: 1_000
+c: 0777
+";
+        let options = LoaderOptions::new().schema(Schema::Yaml11);
+        let out = YamlLoader::load_with_options(s, options.clone()).unwrap();
+        assert_eq!(out[0]["a"].as_bool(), Some(true));
+        assert_eq!(out[0]["b"].as_i64(), Some(1000));
+        assert_eq!(out[0]["c"].as_i64(), Some(0o777));
+
+        // the %YAML directive wins over the option
+        let out = YamlLoader::load_with_options(&format!("%YAML 1.2\n---{}", s), options).unwrap();
+        assert_eq!(out[0]["a"].as_str(), Some("yes"));
+        assert_eq!(out[0]["b"].as_str(), Some("1_000"));
+        assert_eq!(out[0]["c"].as_i64(), Some(777));
+
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0]["a"].as_str(), Some("yes"));
+
+        // so are tagged scalars
+        let s = "[!!bool yes, !!int 0777, !!float 1_0]";
+        let options = LoaderOptions::new().schema(Schema::Yaml11);
+        let out = YamlLoader::load_with_options(s, options.clone()).unwrap();
+        assert_eq!(
+            out[0],
+            Yaml::Array(vec![
+                Yaml::Boolean(true),
+                Yaml::Integer(0o777),
+                Yaml::Real("10".to_owned()),
+            ])
+        );
+        let marked = MarkedYamlLoader::load_with_options(s, options).unwrap();
+        assert_eq!(Yaml::from(marked[0].clone()), out[0]);
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0][0], Yaml::BadValue);
+    }
+
+    #[test]
+    fn test_json_and_failsafe_schemas() {
+        let s = "
+%YAML 1.1
+---
+[null, ~, true, NO, 12, -0, 012, 0x1F, 1e3, -1.5E-2, 1., 9223372036854775808, .inf, '1']
+";
+        let out =
+            YamlLoader::load_with_options(s, LoaderOptions::new().schema(Schema::Json)).unwrap();
+        assert_eq!(
+            out[0],
+            Yaml::Array(vec![
+                Yaml::Null,
+                Yaml::String("~".to_owned()),
+                Yaml::Boolean(true),
+                Yaml::String("NO".to_owned()),
+                Yaml::Integer(12),
+                Yaml::Integer(0),
+                Yaml::String("012".to_owned()),
+                Yaml::String("0x1F".to_owned()),
+                Yaml::Real("1e3".to_owned()),
+                Yaml::Real("-1.5E-2".to_owned()),
+                Yaml::Real("1.".to_owned()),
+                Yaml::Real("9223372036854775808".to_owned()),
+                Yaml::String(".inf".to_owned()),
+                Yaml::String("1".to_owned()),
+            ])
+        );
+
+        let options = LoaderOptions::new().schema(Schema::Failsafe);
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        for node in out[0].as_vec().unwrap() {
+            assert!(node.as_str().is_some(), "{:?}", node);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let s = "
+a: 1
+b:
+  a: 2
+[c]: 3
+a: 4
+[c]: 5
+";
+        let options = LoaderOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let err = YamlLoader::load_with_options(s, options).unwrap_err();
+        assert_eq!(err.marker().line, 6);
+        assert_eq!(err.context_marker().unwrap().line, 2);
+        assert_eq!(
+            err.to_string(),
+            "while loading a mapping, found duplicate key at line 6 column 1, the key first \
+             appears at line 2 column 1"
+        );
+
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0]["a"].as_i64(), Some(4));
+        assert_eq!(out[0]["b"]["a"].as_i64(), Some(2));
+        let c = Yaml::Array(vec![Yaml::String("c".to_owned())]);
+        assert_eq!(out[0].as_hash().unwrap()[&c].as_i64(), Some(5));
+
+        let options = LoaderOptions::new().duplicate_keys(DuplicateKeys::FirstWins);
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        assert_eq!(out[0]["a"].as_i64(), Some(1));
+        assert_eq!(out[0].as_hash().unwrap()[&c].as_i64(), Some(3));
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut s = "a0: &a0 [lol, lol, lol, lol, lol, lol, lol, lol, lol]\n".to_owned();
+        for i in 1..10 {
+            let a = format!("*a{}, ", i - 1).repeat(9);
+            s += &format!("a{}: &a{} [{}]\n", i, i, a.trim_end_matches(", "));
+        }
+        let err =
+            YamlLoader::load_with_options(&s, LoaderOptions::new().max_nodes(100_000)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while loading a document, exceeded the limit of 100000 nodes at line 6 column 10"
+        );
+        // aliases are not expanded when anchors are kept
+        let options = LoaderOptions::new().anchors(true).max_nodes(1000);
+        assert!(YamlLoader::load_with_options(&s, options).is_ok());
+        let options = LoaderOptions::new().anchors(true).max_aliases(80);
+        assert!(YamlLoader::load_with_options(&s, options).is_err());
+
+        let s = "a: [x]\n---\nb: &x 1\nc: *x\n";
+        let options = LoaderOptions::new().max_document_size(20);
+        let out = YamlLoader::load_with_options(s, options.clone()).unwrap();
+        assert_eq!(out[1]["c"].as_i64(), Some(1));
+        assert!(YamlLoader::load_with_options("a: 12345678901234567890\n", options).is_err());
+        let options = LoaderOptions::new().max_nodes(5).max_aliases(1);
+        assert!(YamlLoader::load_with_options(s, options.clone()).is_ok());
+        assert!(MarkedYamlLoader::load_with_options(s, options).is_ok());
+        let options = LoaderOptions::new().max_nodes(4);
+        assert!(YamlLoader::load_with_options(s, options.clone()).is_err());
+        assert!(MarkedYamlLoader::load_with_options(s, options).is_err());
+
+        // documents right at the limit, whatever follows them
+        let options = LoaderOptions::new().max_document_size(11);
+        let s = "a: [x]\n---\nb: [y]\n...\n--- z\n";
+        assert_eq!(
+            YamlLoader::load_with_options(s, options.clone())
+                .unwrap()
+                .len(),
+            3
+        );
+        for end in &["\n--- ", "\n...\n--- "] {
+            let s = "a: [x]".to_owned() + end + &"b".repeat(500) + ": 1\n";
+            let err = YamlLoader::load_with_options(&s, options.clone()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        }
+    }
+
+    // counts the bytes read from it
+    struct CountingReader<'a> {
+        bytes: &'a [u8],
+        read: usize,
+    }
+
+    impl<'a> io::Read for CountingReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.bytes.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_limits_stop_reading() {
+        let s = "- ".to_owned() + &"a".repeat(1_000_000) + "\n" + &"- b\n".repeat(100_000);
+        let options = LoaderOptions::new().max_document_size(100);
+        let mut reader = CountingReader {
+            bytes: s.as_bytes(),
+            read: 0,
+        };
+        let err = YamlLoader::load_from_reader(&mut reader, options.clone()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        assert!(reader.read < 100_000);
+        let err = MarkedYamlLoader::load_with_options(&s, options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while loading a document, exceeded the limit of 100 bytes at line 1 column 166"
+        );
+
+        let s = "- b\n".repeat(1_000_000);
+        let mut reader = CountingReader {
+            bytes: s.as_bytes(),
+            read: 0,
+        };
+        let options = LoaderOptions::new().max_nodes(2);
+        let err = YamlLoader::load_from_reader(&mut reader, options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        assert!(reader.read < 100_000);
+
+        // reported where the source is cut
+        let s = "a: 1\n# ".to_owned() + &"x".repeat(1_000) + "\n";
+        let options = LoaderOptions::new().max_document_size(100);
+        let err = YamlLoader::load_with_options(&s, options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while loading a document, exceeded the limit of 100 bytes at line 2 column 161"
+        );
+    }
+
+    #[test]
+    fn test_load_from_reader() {
+        let s = "a: é\nb: [1, 2]\n";
+        let expected = YamlLoader::load_with_options(s, LoaderOptions::new()).unwrap();
+        let utf16: Vec<u8> = s.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let utf32: Vec<u8> = s.chars().flat_map(|c| u32::from(c).to_le_bytes()).collect();
+        for bytes in &[s.as_bytes(), &utf16, &utf32] {
+            let docs = YamlLoader::load_from_slice(bytes, LoaderOptions::new()).unwrap();
+            assert_eq!(docs, expected);
+        }
+
+        // the stream ends where it is invalid, after a complete document
+        let err = YamlLoader::load_from_slice(b"a: 1\n\xff", LoaderOptions::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidEncoding);
+        assert_eq!(err.marker().line, 2);
+
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+        let err = YamlLoader::load_from_reader(Failing, LoaderOptions::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(err.info(), "while reading the stream, disk on fire");
+    }
+
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<YamlLoader>();
+        assert_send::<Documents<'static, std::str::Chars<'static>>>();
+    }
+
+    #[test]
+    fn test_documents() {
+        let s = "a: &x 1\nb: *x\n---\n- &x 2\n- *x\n---\n# empty\n...\n--- c\n";
+        let mut loader = YamlLoader::new(LoaderOptions::new());
+        let docs: Vec<_> = loader.documents(s.chars()).map(Result::unwrap).collect();
+        assert_eq!(
+            docs,
+            YamlLoader::load_with_options(s, LoaderOptions::new()).unwrap()
+        );
+        assert_eq!(docs.len(), 4);
+        assert_eq!(docs[1][1].as_i64(), Some(2));
+        assert!(loader.anchor_map.len() <= 1);
+
+        let mut loader = YamlLoader::new(LoaderOptions::new().multi_document(false));
+        assert_eq!(loader.documents(s.chars()).count(), 1);
+
+        let s = "a: 1\n---\na: 1\na: 2\n---\nb: 3\n";
+        let options = LoaderOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let mut loader = YamlLoader::new(options);
+        let results: Vec<_> = loader.documents(s.chars()).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            ErrorKind::DuplicateKey
+        );
+
+        // the loader can be reused after an error
+        let mut loader = YamlLoader::new(LoaderOptions::new());
+        let err = loader.documents("a:\n  b: [1, 2\n".chars()).next().unwrap();
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        let mut docs = loader.documents("x: 1\n".chars());
+        assert_eq!(docs.next().unwrap().unwrap()["x"].as_i64(), Some(1));
+        assert!(docs.next().is_none());
+    }
+
+    #[test]
+    fn test_tag_directives() {
+        let s = "
+%TAG !e! tag:example.com,2000:app/
+%TAG !yaml! tag:yaml.org,2002:
+---
+- !e!foo bar
+- !yaml!int 12
+- !local baz
+- !<tag:example.com,2000:app/foo> qux
+";
+        let options = LoaderOptions::new()
+            .tags(true)
+            .constructor("tag:example.com,2000:app/foo", |node: Yaml| {
+                Ok(Yaml::String(node.into_string().unwrap().to_uppercase()))
+            });
+        let out = YamlLoader::load_with_options(s, options).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc[0].as_str().unwrap(), "BAR");
+        assert_eq!(doc[1].as_i64().unwrap(), 12);
+        assert_eq!(
+            doc[2],
+            Yaml::Tagged(
+                "!local".to_owned(),
+                Box::new(Yaml::String("baz".to_owned()))
+            )
+        );
+        assert_eq!(doc[3].as_str().unwrap(), "QUX");
+    }
+
     #[test]
     fn test_github_27() {
         // https://github.com/chyh1990/yaml-rust/issues/27
@@ -818,4 +1770,14 @@ subcommands3:
         let s = "[".repeat(10_000) + &"]".repeat(10_000);
         assert!(YamlLoader::load_from_str(&s).is_err());
     }
+
+    #[test]
+    fn test_recursion_depth_check_blocks() {
+        let s = "- ".repeat(10_000) + "a";
+        assert!(YamlLoader::load_from_str(&s).is_err());
+        let options = LoaderOptions::new().max_depth(2);
+        assert!(YamlLoader::load_with_options("a: [b]", options.clone()).is_ok());
+        assert!(YamlLoader::load_with_options("a: [[b]]", options.clone()).is_err());
+        assert!(MarkedYamlLoader::load_with_options("a: [[b]]", options).is_err());
+    }
 }
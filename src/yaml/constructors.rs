@@ -0,0 +1,43 @@
+use super::Schema;
+use super::Yaml;
+use std::sync::Arc;
+
+/// Builds the value of a node with a given tag, see
+/// `LoaderOptions::constructor`.
+///
+/// Constructors receive scalars as `Yaml::String` holding the raw value, and
+/// collections as `Yaml::Array` or `Yaml::Hash` whose children have already
+/// been loaded. Returning an error aborts loading with the given message.
+pub type Constructor = Arc<dyn Fn(Yaml) -> Result<Yaml, String> + Send + Sync>;
+
+/// Scalar tags of the core schema, which the loaders construct by themselves
+/// unless a constructor is registered for them.
+pub(crate) const CORE_SCALAR_TAGS: [&str; 5] = ["!!bool", "!!int", "!!float", "!!null", "!!str"];
+
+/// Build the value of a scalar tagged with one of `CORE_SCALAR_TAGS`,
+/// resolving it with the `schema` of the document, e.g. so that `!!int 0777`
+/// is octal in a YAML 1.1 document.
+pub(crate) fn construct_core(tag: &str, node: Yaml, schema: Schema) -> Yaml {
+    // the failsafe schema has no types to resolve tagged scalars to
+    let schema = match schema {
+        Schema::Failsafe => Schema::Core,
+        schema => schema,
+    };
+    let resolved = match node {
+        Yaml::String(ref v) if tag != "!!str" => schema.resolve(v),
+        _ => Yaml::BadValue,
+    };
+    // values that cannot be constructed load as `Yaml::BadValue`
+    match (tag, resolved) {
+        ("!!bool", Yaml::Boolean(v)) => Yaml::Boolean(v),
+        ("!!int", Yaml::Integer(v)) => Yaml::Integer(v),
+        ("!!float", Yaml::Real(v)) => Yaml::Real(v),
+        ("!!float", Yaml::Integer(v)) => Yaml::Real(v.to_string()),
+        ("!!null", Yaml::Null) => Yaml::Null,
+        ("!!str", _) => match node {
+            Yaml::String(_) => node,
+            _ => Yaml::BadValue,
+        },
+        _ => Yaml::BadValue,
+    }
+}
@@ -0,0 +1,224 @@
+use super::marked::MarkedNode;
+use super::marked::MarkedYaml;
+use super::LoaderOptions;
+use super::Yaml;
+use crate::parser::AnchorID;
+use crate::parser::Event;
+use crate::parser::Parser;
+use crate::parser::TryEventReceiver;
+use crate::scanner::ErrorKind;
+use crate::scanner::Marker;
+use crate::scanner::Position;
+use crate::scanner::ScanError;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Bytes the scanner may read past the end of a document before the parser
+/// reports that it ended, e.g. the `---` starting the next one.
+const READ_AHEAD: usize = 64;
+
+/// Nodes, aliases and bytes of the document being loaded, checked against the
+/// limits of `LoaderOptions`.
+///
+/// The size of documents is checked at each event, and the source of the
+/// loader is bounded so that the scanner stops reading a document exceeding
+/// it, e.g. in the middle of a huge scalar.
+pub(crate) struct DocumentBudget {
+    max_document_size: Option<usize>,
+    // byte offset of the end of the previous document
+    start: usize,
+    nodes: usize,
+    aliases: usize,
+    // where the source stops
+    bound: Arc<SourceBound>,
+}
+
+/// How far a `BoundedSource` reads, shared with its `DocumentBudget`.
+#[derive(Default)]
+struct SourceBound {
+    end: AtomicUsize,
+    // where the source was cut
+    cut: Mutex<Option<Marker>>,
+}
+
+/// Source of a loader, which stops where its `DocumentBudget` tells.
+pub(crate) struct BoundedSource<T> {
+    source: T,
+    position: Position,
+    bound: Arc<SourceBound>,
+}
+
+impl<T: Iterator<Item = char>> Iterator for BoundedSource<T> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.position.marker().byte_offset > self.bound.end.load(Ordering::Relaxed) {
+            self.position.advance(None);
+            *self.bound.cut.lock().unwrap() = Some(self.position.marker());
+            return None;
+        }
+        let c = self.source.next();
+        self.position.advance(c);
+        c
+    }
+}
+
+/// Loader whose documents are checked against the limits of its options by
+/// its `DocumentBudget`.
+pub(crate) trait BudgetedLoader: TryEventReceiver + Sized {
+    fn options(&self) -> &LoaderOptions;
+
+    fn budget(&self) -> &DocumentBudget;
+
+    /// Drop the state of the documents being loaded.
+    fn reset(&mut self);
+
+    /// Parser feeding the loader with the events of `source`, read up to the
+    /// first document exceeding the size limit.
+    fn bounded_parser<T: Iterator<Item = char>>(
+        &mut self,
+        source: T,
+    ) -> Parser<'_, BoundedSource<T>, Self> {
+        let with_comments = self.options().comments;
+        let max_depth = self.options().max_depth;
+        self.reset();
+        let source = self.budget().source(source);
+        Parser::new(source, self, with_comments).max_depth(max_depth)
+    }
+
+    /// Load the documents of `source`, or the first of them unless the
+    /// options tell otherwise.
+    fn load_source<T: Iterator<Item = char>>(&mut self, source: T) -> Result<(), ScanError> {
+        let multi_document = self.options().multi_document;
+        let result = self.bounded_parser(source).load(multi_document);
+
+        // the parser sees the stream end where the source is cut
+        self.budget().check_source(result)
+    }
+}
+
+impl DocumentBudget {
+    pub(crate) fn new(options: &LoaderOptions) -> DocumentBudget {
+        let budget = DocumentBudget {
+            max_document_size: options.max_document_size,
+            start: 0,
+            nodes: 0,
+            aliases: 0,
+            bound: Arc::default(),
+        };
+        budget.bound_source();
+        budget
+    }
+
+    /// Bound `source` to the documents within the size limit.
+    pub(crate) fn source<T>(&self, source: T) -> BoundedSource<T> {
+        BoundedSource {
+            source,
+            position: Position::new(),
+            bound: self.bound.clone(),
+        }
+    }
+
+    /// Account for the event received at `mark`. Aliases cost the number of
+    /// nodes of their anchored node, given by `alias_nodes`, as they are
+    /// loaded as copies of it.
+    pub(crate) fn on_event<F>(
+        &mut self,
+        options: &LoaderOptions,
+        ev: &Event,
+        mark: Marker,
+        alias_nodes: F,
+    ) -> Result<(), ScanError>
+    where
+        F: FnOnce(AnchorID) -> usize,
+    {
+        self.check_source(Ok(()))?;
+        check(
+            self.max_document_size,
+            mark.byte_offset - self.start,
+            "bytes",
+            mark,
+        )?;
+        let nodes = match *ev {
+            Event::DocumentStart(_) => {
+                self.nodes = 0;
+                self.aliases = 0;
+                return Ok(());
+            }
+            Event::DocumentEnd => {
+                self.start = mark.byte_offset;
+                self.bound_source();
+                return Ok(());
+            }
+            Event::Scalar(..) | Event::SequenceStart(..) | Event::MappingStart(..) => 1,
+            Event::Alias(id) => {
+                self.aliases += 1;
+                check(options.max_aliases, self.aliases, "aliases", mark)?;
+                alias_nodes(id)
+            }
+            _ => return Ok(()),
+        };
+        self.nodes = self.nodes.saturating_add(nodes);
+        check(options.max_nodes, self.nodes, "nodes", mark)
+    }
+
+    /// Check the `result` of the parser: if the source was cut, the parser
+    /// saw the stream end there, and fails because of the size limit.
+    pub(crate) fn check_source(&self, result: Result<(), ScanError>) -> Result<(), ScanError> {
+        match *self.bound.cut.lock().unwrap() {
+            Some(cut) => check(self.max_document_size, usize::MAX, "bytes", cut),
+            None => result,
+        }
+    }
+
+    fn bound_source(&self) {
+        let end = match self.max_document_size {
+            Some(size) => self.start.saturating_add(size).saturating_add(READ_AHEAD),
+            None => usize::MAX,
+        };
+        self.bound.end.store(end, Ordering::Relaxed);
+    }
+}
+
+fn check(limit: Option<usize>, count: usize, what: &str, mark: Marker) -> Result<(), ScanError> {
+    match limit {
+        Some(limit) if count > limit => Err(ScanError::new(
+            mark,
+            &format!(
+                "while loading a document, exceeded the limit of {} {}",
+                limit, what
+            ),
+        )
+        .with_kind(ErrorKind::LimitExceeded)),
+        _ => Ok(()),
+    }
+}
+
+/// Number of nodes of `node`, counting collections and the nodes they hold.
+pub(crate) fn yaml_nodes(node: &Yaml) -> usize {
+    match *node {
+        Yaml::Array(ref v) => v.iter().map(yaml_nodes).fold(1, usize::saturating_add),
+        Yaml::Hash(ref h) => h
+            .iter()
+            .filter(|(k, _)| !k.is_comment())
+            .map(|(k, v)| yaml_nodes(k).saturating_add(yaml_nodes(v)))
+            .fold(1, usize::saturating_add),
+        Yaml::Anchor(_, ref node) | Yaml::Tagged(_, ref node) => yaml_nodes(node),
+        Yaml::Comment(..) => 0,
+        _ => 1,
+    }
+}
+
+/// See `yaml_nodes`.
+pub(crate) fn marked_nodes(node: &MarkedYaml) -> usize {
+    match node.node {
+        MarkedNode::Array(ref v) => v.iter().map(marked_nodes).fold(1, usize::saturating_add),
+        MarkedNode::Hash(ref h) => h
+            .iter()
+            .map(|(k, v)| marked_nodes(k).saturating_add(marked_nodes(v)))
+            .fold(1, usize::saturating_add),
+        _ => 1,
+    }
+}
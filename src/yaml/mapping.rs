@@ -0,0 +1,200 @@
+use super::marked::MarkedHash;
+use super::marked::MarkedNode;
+use super::marked::MarkedYaml;
+use super::DuplicateKeys;
+use super::Hash;
+use super::Yaml;
+use crate::scanner::ErrorKind;
+use crate::scanner::Marker;
+use crate::scanner::ScanError;
+use linked_hash_map::LinkedHashMap;
+use std::collections::HashMap;
+use std::hash;
+use std::mem;
+
+/// Node built by a loader, e.g. `Yaml` for `YamlLoader` and `MarkedYaml` for
+/// `MarkedYamlLoader`, so that both load mappings the same way.
+pub(crate) trait LoadedNode: Clone + Eq + hash::Hash {
+    /// Collect the mappings a merge key value refers to: either a mapping or
+    /// a sequence of mappings.
+    fn into_merge_sources(self) -> Option<Vec<LinkedHashMap<Self, Self>>>;
+
+    /// Determine if the node is a merge key left as is, see
+    /// `LoaderOptions::merge_keys`. Only `YamlLoader` leaves them as is.
+    fn is_merge_key(&self) -> bool {
+        false
+    }
+
+    /// Add `value` to the value of a merge key left as is, so that the
+    /// mappings of a repeated merge key are merged too.
+    fn append_merge_value(&mut self, _value: Self) {}
+}
+
+impl LoadedNode for Yaml {
+    fn into_merge_sources(self) -> Option<Vec<Hash>> {
+        // comments are not merged
+        let without_comments =
+            |h: Hash| -> Hash { h.into_iter().filter(|(key, _)| !key.is_comment()).collect() };
+        match self {
+            Yaml::Hash(h) => Some(vec![without_comments(h)]),
+            Yaml::Array(v) => v
+                .into_iter()
+                .filter(|node| !node.is_comment())
+                .map(|node| node.into_hash().map(without_comments))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn is_merge_key(&self) -> bool {
+        match *self {
+            Yaml::Tagged(ref tag, ref node) => tag == "!!merge" && node.as_str() == Some("<<"),
+            _ => false,
+        }
+    }
+
+    fn append_merge_value(&mut self, value: Yaml) {
+        // `<<: *a` then `<<: *b` merge like `<<: [*a, *b]`
+        let mut values = match mem::replace(self, Yaml::BadValue) {
+            Yaml::Array(v) => v,
+            first => vec![first],
+        };
+        match value {
+            Yaml::Array(v) => values.extend(v),
+            value => values.push(value),
+        }
+        *self = Yaml::Array(values);
+    }
+}
+
+impl LoadedNode for MarkedYaml {
+    fn into_merge_sources(self) -> Option<Vec<MarkedHash>> {
+        match self.node {
+            MarkedNode::Hash(h) => Some(vec![h]),
+            MarkedNode::Array(v) => v
+                .into_iter()
+                .map(|node| match node.node {
+                    MarkedNode::Hash(h) => Some(h),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Keys and merge keys (`<<`) of a mapping of `N` being loaded.
+pub(crate) struct MappingState<N> {
+    // key waiting for its value
+    key: Option<N>,
+    // marker of each key, to report duplicates
+    key_marks: HashMap<N, Marker>,
+    // the next node is the value of a merge key
+    merge_pending: bool,
+    // mappings to merge, in order of precedence
+    merge_sources: Vec<LinkedHashMap<N, N>>,
+}
+
+impl<N> Default for MappingState<N> {
+    fn default() -> MappingState<N> {
+        MappingState {
+            key: None,
+            key_marks: HashMap::new(),
+            merge_pending: false,
+            merge_sources: Vec::new(),
+        }
+    }
+}
+
+impl<N: LoadedNode> MappingState<N> {
+    /// Determine if the next node is a key.
+    pub(crate) fn is_key_expected(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// Take the next node as the value of a merge key.
+    pub(crate) fn expect_merge(&mut self) {
+        self.merge_pending = true;
+    }
+
+    /// Insert `node`, found at `mark`, into `hash` as the next key or value.
+    pub(crate) fn insert(
+        &mut self,
+        hash: &mut LinkedHashMap<N, N>,
+        node: N,
+        mark: Marker,
+        duplicate_keys: DuplicateKeys,
+    ) -> Result<(), ScanError> {
+        // current node is the value of a merge key
+        if self.merge_pending {
+            self.merge_pending = false;
+            let sources = node.into_merge_sources().ok_or_else(|| {
+                ScanError::new(
+                    mark,
+                    "while merging keys, expected a mapping or a sequence of mappings",
+                )
+                .with_kind(ErrorKind::InvalidMerge)
+            })?;
+            self.merge_sources.extend(sources);
+            return Ok(());
+        }
+        match self.key.take() {
+            // current node is a key, merge keys may be repeated
+            None => {
+                if duplicate_keys == DuplicateKeys::Error && !node.is_merge_key() {
+                    if let Some(first) = self.key_marks.get(&node) {
+                        return Err(ScanError::new(
+                            mark,
+                            "while loading a mapping, found duplicate key",
+                        )
+                        .with_kind(ErrorKind::DuplicateKey)
+                        .with_context(*first, "the key first appears"));
+                    }
+                    self.key_marks.insert(node.clone(), mark);
+                }
+                self.key = Some(node);
+            }
+            // current node is a value
+            Some(key) => {
+                if key.is_merge_key() {
+                    if let Some(value) = hash.get_mut(&key) {
+                        value.append_merge_value(node);
+                        return Ok(());
+                    }
+                }
+                if duplicate_keys != DuplicateKeys::FirstWins || !hash.contains_key(&key) {
+                    hash.insert(key, node);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the mappings of the merge keys of the mapping into `hash`, once
+    /// it is loaded.
+    pub(crate) fn finish(self, hash: &mut LinkedHashMap<N, N>) {
+        if !self.merge_sources.is_empty() {
+            merge_hash(hash, self.merge_sources.into_iter().flatten());
+        }
+    }
+}
+
+/// Merge the `entries` of the merged mappings into `hash` following
+/// https://yaml.org/type/merge.html: keys explicitly present in `hash` win,
+/// and earlier entries win over later ones. Merged keys are placed before the
+/// explicit ones.
+fn merge_hash<K, V, I>(hash: &mut LinkedHashMap<K, V>, entries: I)
+where
+    K: Eq + hash::Hash,
+    I: IntoIterator<Item = (K, V)>,
+{
+    let mut merged = LinkedHashMap::new();
+    for (key, value) in entries {
+        if hash.contains_key(&key) || merged.contains_key(&key) {
+            continue;
+        }
+        merged.insert(key, value);
+    }
+    merged.extend(mem::take(hash));
+    *hash = merged;
+}
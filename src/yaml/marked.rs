@@ -0,0 +1,564 @@
+use super::limits::marked_nodes;
+use super::limits::BudgetedLoader;
+use super::limits::DocumentBudget;
+use super::mapping::MappingState;
+use super::parse_f64;
+use super::tag_shorthand;
+use super::LoaderOptions;
+use super::Meta;
+use super::Yaml;
+use crate::parser::Event;
+use crate::parser::Tag;
+use crate::parser::TryEventReceiver;
+use crate::scanner::Marker;
+use crate::scanner::ScanError;
+use crate::scanner::Span;
+use crate::scanner::TScalarStyle;
+use linked_hash_map::LinkedHashMap;
+use std::collections::BTreeMap;
+use std::hash;
+use std::ops::Index;
+
+pub type MarkedHash = LinkedHashMap<MarkedYaml, MarkedYaml>;
+
+/// A node loaded by `MarkedYamlLoader`, along with its position in the
+/// source.
+///
+/// Nodes are compared and hashed by value only, so that keys of a
+/// `MarkedHash` can be looked up wherever they appear.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::MarkedYamlLoader;
+///
+/// let docs = MarkedYamlLoader::load_from_str("replicas: -1").unwrap();
+/// let replicas = &docs[0]["replicas"];
+/// assert_eq!(replicas.as_i64(), Some(-1));
+/// assert_eq!((replicas.start.line, replicas.start.col), (1, 10));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MarkedYaml {
+    pub node: MarkedNode,
+    /// Position of the first character of the node.
+    pub start: Marker,
+    /// Position right after the node. Block collections end with their last
+    /// entry.
+    pub end: Marker,
+}
+
+/// Value of a `MarkedYaml`, see `Yaml` for the meaning of each variant.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MarkedNode {
+    Real(String),
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+    Array(Vec<MarkedYaml>),
+    Hash(MarkedHash),
+    Null,
+    BadValue,
+}
+
+impl PartialEq for MarkedYaml {
+    fn eq(&self, other: &MarkedYaml) -> bool {
+        self.node == other.node
+    }
+}
+
+impl Eq for MarkedYaml {}
+
+impl hash::Hash for MarkedYaml {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.node.hash(state)
+    }
+}
+
+impl MarkedYaml {
+    /// Convert `yaml` into a `MarkedYaml` whose nodes all span from `start`
+    /// to `end`. Comments and aliases become `MarkedNode::BadValue`, and the
+    /// properties of nodes are dropped.
+    pub fn from_yaml(yaml: Yaml, start: Marker, end: Marker) -> MarkedYaml {
+        let node = match yaml {
+            Yaml::Real(v) => MarkedNode::Real(v),
+            Yaml::Integer(v) => MarkedNode::Integer(v),
+            Yaml::String(v) => MarkedNode::String(v),
+            Yaml::Boolean(v) => MarkedNode::Boolean(v),
+            Yaml::Array(v) => MarkedNode::Array(
+                v.into_iter()
+                    .filter(|node| !node.is_comment())
+                    .map(|node| MarkedYaml::from_yaml(node, start, end))
+                    .collect(),
+            ),
+            Yaml::Hash(h) => MarkedNode::Hash(
+                h.into_iter()
+                    .filter(|(key, _)| !key.is_comment())
+                    .map(|(key, value)| {
+                        (
+                            MarkedYaml::from_yaml(key, start, end),
+                            MarkedYaml::from_yaml(value, start, end),
+                        )
+                    })
+                    .collect(),
+            ),
+            Yaml::Null => MarkedNode::Null,
+            Yaml::Anchor(_, node) | Yaml::Tagged(_, node) => {
+                return MarkedYaml::from_yaml(*node, start, end)
+            }
+            Yaml::Meta(Meta::Integer(_, node))
+            | Yaml::Meta(Meta::String(_, node))
+            | Yaml::Meta(Meta::Collection(_, node)) => {
+                return MarkedYaml::from_yaml(*node, start, end)
+            }
+            Yaml::Alias(_) | Yaml::Comment(..) | Yaml::BadValue => MarkedNode::BadValue,
+        };
+        MarkedYaml { node, start, end }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.node {
+            MarkedNode::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.node {
+            MarkedNode::Integer(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.node {
+            MarkedNode::Real(ref v) => parse_f64(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.node {
+            MarkedNode::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec(&self) -> Option<&Vec<MarkedYaml>> {
+        match self.node {
+            MarkedNode::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_hash(&self) -> Option<&MarkedHash> {
+        match self.node {
+            MarkedNode::Hash(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.node, MarkedNode::Null)
+    }
+
+    pub fn is_badvalue(&self) -> bool {
+        matches!(self.node, MarkedNode::BadValue)
+    }
+}
+
+impl From<MarkedYaml> for Yaml {
+    fn from(marked: MarkedYaml) -> Yaml {
+        match marked.node {
+            MarkedNode::Real(v) => Yaml::Real(v),
+            MarkedNode::Integer(v) => Yaml::Integer(v),
+            MarkedNode::String(v) => Yaml::String(v),
+            MarkedNode::Boolean(v) => Yaml::Boolean(v),
+            MarkedNode::Array(v) => Yaml::Array(v.into_iter().map(Yaml::from).collect()),
+            MarkedNode::Hash(h) => Yaml::Hash(
+                h.into_iter()
+                    .map(|(key, value)| (Yaml::from(key), Yaml::from(value)))
+                    .collect(),
+            ),
+            MarkedNode::Null => Yaml::Null,
+            MarkedNode::BadValue => Yaml::BadValue,
+        }
+    }
+}
+
+static BAD_VALUE: MarkedYaml = MarkedYaml {
+    node: MarkedNode::BadValue,
+    start: Marker {
+        index: 0,
+        line: 0,
+        col: 0,
+        byte_offset: 0,
+    },
+    end: Marker {
+        index: 0,
+        line: 0,
+        col: 0,
+        byte_offset: 0,
+    },
+};
+
+impl<'a> Index<&'a str> for MarkedYaml {
+    type Output = MarkedYaml;
+
+    fn index(&self, idx: &'a str) -> &MarkedYaml {
+        let key = MarkedYaml {
+            node: MarkedNode::String(idx.to_owned()),
+            ..BAD_VALUE.clone()
+        };
+        match self.as_hash() {
+            Some(h) => h.get(&key).unwrap_or(&BAD_VALUE),
+            None => &BAD_VALUE,
+        }
+    }
+}
+
+impl Index<usize> for MarkedYaml {
+    type Output = MarkedYaml;
+
+    fn index(&self, idx: usize) -> &MarkedYaml {
+        if let Some(v) = self.as_vec() {
+            v.get(idx).unwrap_or(&BAD_VALUE)
+        } else if let Some(v) = self.as_hash() {
+            let key = MarkedYaml {
+                node: MarkedNode::Integer(idx as i64),
+                ..BAD_VALUE.clone()
+            };
+            v.get(&key).unwrap_or(&BAD_VALUE)
+        } else {
+            &BAD_VALUE
+        }
+    }
+}
+
+/// Loader building `MarkedYaml` documents, so that values can be reported
+/// along with their position in the source.
+///
+/// It follows the `LoaderOptions` like `YamlLoader`, except that comments are
+/// never loaded, aliases are always resolved to copies of the anchored nodes
+/// and tags without a constructor are dropped.
+pub struct MarkedYamlLoader {
+    docs: Vec<MarkedYaml>,
+    // states
+    // (current node, anchor_id, tag) tuple
+    doc_stack: Vec<(MarkedYaml, usize, Option<Tag>)>,
+    mappings: Vec<MappingState<MarkedYaml>>,
+    // anchored node and its number of nodes
+    anchor_map: BTreeMap<usize, (MarkedYaml, usize)>,
+    options: LoaderOptions,
+    // version of the current document, from its %YAML directive
+    version: Option<(u32, u32)>,
+    budget: DocumentBudget,
+    error: Option<ScanError>,
+}
+
+impl TryEventReceiver for MarkedYamlLoader {
+    fn on_event(&mut self, ev: Event, span: Span) -> Result<(), ScanError> {
+        let anchor_map = &self.anchor_map;
+        self.budget.on_event(&self.options, &ev, span.start, |id| {
+            anchor_map.get(&id).map_or(1, |(_, nodes)| *nodes)
+        })?;
+        self.load_event(ev, span);
+        // errors of the loader stop the parser
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl MarkedYamlLoader {
+    fn load_event(&mut self, ev: Event, span: Span) {
+        match ev {
+            Event::DocumentStart(version) => {
+                self.version = version;
+                // anchors are scoped to their document
+                self.anchor_map.clear();
+            }
+            Event::DocumentEnd => match self.doc_stack.len() {
+                // empty document
+                0 => self.docs.push(MarkedYaml {
+                    node: MarkedNode::BadValue,
+                    start: span.start,
+                    end: span.start,
+                }),
+                1 => self.docs.push(self.doc_stack.pop().unwrap().0),
+                _ => unreachable!(),
+            },
+            Event::SequenceStart(aid, tag) => {
+                let node = MarkedYaml {
+                    node: MarkedNode::Array(Vec::new()),
+                    start: span.start,
+                    end: span.start,
+                };
+                self.doc_stack.push((node, aid, tag));
+            }
+            Event::MappingStart(aid, tag) => {
+                let node = MarkedYaml {
+                    node: MarkedNode::Hash(MarkedHash::new()),
+                    start: span.start,
+                    end: span.start,
+                };
+                self.doc_stack.push((node, aid, tag));
+                self.mappings.push(MappingState::default());
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (mut node, aid, tag) = self.doc_stack.pop().unwrap();
+                node.end = span.end;
+                if let MarkedNode::Hash(ref mut h) = node.node {
+                    self.mappings.pop().unwrap().finish(h);
+                }
+                if let Some(node) = self.apply_tag(node, tag) {
+                    self.insert_new_node(node, aid);
+                }
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                if self.options.merge_keys
+                    && style == TScalarStyle::Plain
+                    && tag.is_none()
+                    && v == "<<"
+                    && self.is_key_expected()
+                {
+                    self.mappings.last_mut().unwrap().expect_merge();
+                    return;
+                }
+                let node = if style != TScalarStyle::Plain || tag.is_some() {
+                    Yaml::String(v)
+                } else {
+                    // Datatype is not specified
+                    self.options.schema.for_version(self.version).resolve(&v)
+                };
+                let node = MarkedYaml::from_yaml(node, span.start, span.end);
+                if let Some(node) = self.apply_tag(node, tag) {
+                    self.insert_new_node(node, aid);
+                }
+            }
+            Event::Alias(id) => {
+                let node = match self.anchor_map.get(&id) {
+                    Some((v, _)) => MarkedYaml {
+                        start: span.start,
+                        end: span.end,
+                        ..v.clone()
+                    },
+                    None => MarkedYaml {
+                        node: MarkedNode::BadValue,
+                        start: span.start,
+                        end: span.end,
+                    },
+                };
+                self.insert_new_node(node, 0);
+            }
+            _ => { /* ignore */ }
+        }
+    }
+
+    fn insert_new_node(&mut self, node: MarkedYaml, aid: usize) {
+        // valid anchor id starts from 1
+        if aid > 0 {
+            self.anchor_map
+                .insert(aid, (node.clone(), marked_nodes(&node)));
+        }
+        let (parent, _, _) = match self.doc_stack.last_mut() {
+            Some(parent) => parent,
+            None => {
+                self.doc_stack.push((node, aid, None));
+                return;
+            }
+        };
+        match parent.node {
+            MarkedNode::Array(ref mut v) => v.push(node),
+            MarkedNode::Hash(ref mut h) => {
+                let mapping = self.mappings.last_mut().unwrap();
+                let mark = node.start;
+                if let Err(e) = mapping.insert(h, node, mark, self.options.duplicate_keys) {
+                    self.error = Some(e);
+                }
+            }
+            _ => unreachable!("should never be executed: parent={:?}", parent),
+        }
+    }
+
+    /// Build the value of a tagged node with the constructor of its tag, see
+    /// `LoaderOptions::constructor_for`. The nodes built by constructors span
+    /// the tagged node. Returns `None` if the constructor failed.
+    fn apply_tag(&mut self, node: MarkedYaml, tag: Option<Tag>) -> Option<MarkedYaml> {
+        let constructor = match tag {
+            Some(tag) => self
+                .options
+                .constructor_for(&tag_shorthand(&tag.uri), self.version),
+            None => None,
+        };
+        let constructor = match constructor {
+            Some(constructor) => constructor,
+            None => return Some(node),
+        };
+        let (start, end) = (node.start, node.end);
+        match constructor(Yaml::from(node)) {
+            Ok(node) => Some(MarkedYaml::from_yaml(node, start, end)),
+            Err(info) => {
+                self.error = Some(ScanError::new(start, &info));
+                None
+            }
+        }
+    }
+
+    fn is_key_expected(&self) -> bool {
+        match self.doc_stack.last() {
+            Some((
+                MarkedYaml {
+                    node: MarkedNode::Hash(_),
+                    ..
+                },
+                _,
+                _,
+            )) => self.mappings.last().unwrap().is_key_expected(),
+            _ => false,
+        }
+    }
+
+    pub fn load_from_str(source: &str) -> Result<Vec<MarkedYaml>, ScanError> {
+        MarkedYamlLoader::load_with_options(source, LoaderOptions::default())
+    }
+
+    /// Load the documents of `source` as configured by `options`.
+    pub fn load_with_options(
+        source: &str,
+        options: LoaderOptions,
+    ) -> Result<Vec<MarkedYaml>, ScanError> {
+        let mut loader = MarkedYamlLoader {
+            docs: Vec::new(),
+            doc_stack: Vec::new(),
+            mappings: Vec::new(),
+            anchor_map: BTreeMap::new(),
+            budget: DocumentBudget::new(&options),
+            options,
+            version: None,
+            error: None,
+        };
+        loader.load_source(source.chars())?;
+        Ok(loader.docs)
+    }
+}
+
+impl BudgetedLoader for MarkedYamlLoader {
+    fn options(&self) -> &LoaderOptions {
+        &self.options
+    }
+
+    fn budget(&self) -> &DocumentBudget {
+        &self.budget
+    }
+
+    fn reset(&mut self) {
+        self.docs.clear();
+        self.doc_stack.clear();
+        self.mappings.clear();
+        self.anchor_map.clear();
+        self.version = None;
+        self.budget = DocumentBudget::new(&self.options);
+        self.error = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::ErrorKind;
+    use crate::DuplicateKeys;
+
+    #[test]
+    fn test_markers() {
+        let s = "
+a: 1
+b: [x, {c: y}]
+seq:
+  - &anchor foo
+  - *anchor
+";
+        let out = MarkedYamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+
+        let a = &doc["a"];
+        assert_eq!(a.as_i64(), Some(1));
+        assert_eq!((a.start.line, a.start.col), (2, 3));
+        assert_eq!((a.end.line, a.end.col), (2, 4));
+
+        let b = &doc["b"];
+        assert_eq!((b.start.line, b.start.col), (3, 3));
+        assert_eq!((b.end.line, b.end.col), (3, 14));
+        assert_eq!((b[1].start.line, b[1].start.col), (3, 7));
+        assert_eq!((b[1]["c"].start.line, b[1]["c"].start.col), (3, 11));
+
+        let seq = &doc["seq"];
+        assert_eq!(seq[0].as_str(), Some("foo"));
+        assert_eq!((seq[0].start.line, seq[0].start.col), (5, 12));
+        assert_eq!(seq[1].as_str(), Some("foo"));
+        assert_eq!((seq[0].end.line, seq[0].end.col), (5, 15));
+        assert_eq!((seq[1].start.line, seq[1].start.col), (6, 4));
+        assert_eq!((seq[1].end.line, seq[1].end.col), (6, 11));
+        assert_eq!(seq.end, seq[1].end);
+        assert_eq!(doc.end, seq[1].end);
+
+        let keys: Vec<_> = doc
+            .as_hash()
+            .unwrap()
+            .keys()
+            .map(|key| key.start.line)
+            .collect();
+        assert_eq!(keys, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_options() {
+        let s = "
+base: &base {x: 1, z: 2}
+derived:
+  <<: *base
+  z: !!str 3
+  when: yes
+";
+        let options = LoaderOptions::new()
+            .merge_keys(true)
+            .schema(crate::Schema::Yaml11);
+        let out = MarkedYamlLoader::load_with_options(s, options).unwrap();
+        let derived = &out[0]["derived"];
+        assert_eq!(derived["x"].as_i64(), Some(1));
+        assert_eq!(derived["x"].start.line, 2);
+        assert_eq!(derived["z"].as_str(), Some("3"));
+        assert_eq!(derived["when"].as_bool(), Some(true));
+
+        let expected = crate::YamlLoader::load_with_options(
+            s,
+            LoaderOptions::new()
+                .merge_keys(true)
+                .schema(crate::Schema::Yaml11),
+        )
+        .unwrap();
+        assert_eq!(Yaml::from(out[0].clone()), expected[0]);
+
+        let options = LoaderOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let err = MarkedYamlLoader::load_with_options("a: 1\nb: 2\na: 3", options).unwrap_err();
+        assert_eq!(err.marker().line, 3);
+        assert_eq!(err.context_marker().unwrap().line, 1);
+    }
+
+    #[test]
+    fn test_anchors_per_document() {
+        let out = MarkedYamlLoader::load_from_str("a: &x 1\n---\n[&x 2, *x]\n").unwrap();
+        assert_eq!(out[1][1].as_i64(), Some(2));
+        assert_eq!(out[1][1].start.line, 3);
+
+        for &(s, line) in &[
+            ("a: &x 1\n---\nb: *x\n", 3),
+            ("a: &x {c: 1}\n---\nb:\n  <<: *x\n", 4),
+        ] {
+            let options = LoaderOptions::new().merge_keys(true);
+            let err = MarkedYamlLoader::load_with_options(s, options).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::UnknownAnchor);
+            assert_eq!(err.marker().line, line);
+        }
+    }
+}
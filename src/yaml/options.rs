@@ -0,0 +1,304 @@
+use super::constructors::construct_core;
+use super::constructors::Constructor;
+use super::constructors::CORE_SCALAR_TAGS;
+use super::schema::Schema;
+use super::tag_shorthand;
+use super::Yaml;
+use crate::parser::DEFAULT_MAX_DEPTH;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// What `YamlLoader` does with a key appearing twice in a mapping.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DuplicateKeys {
+    /// Fail to load, reporting both occurrences of the key.
+    Error,
+    /// Keep the value of the last occurrence.
+    #[default]
+    LastWins,
+    /// Keep the value of the first occurrence.
+    FirstWins,
+}
+
+/// Options controlling how `YamlLoader` turns a YAML stream into documents.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::{LoaderOptions, YamlLoader};
+///
+/// let options = LoaderOptions::new().comments(true);
+/// let docs = YamlLoader::load_with_options("a: 1 # one", options).unwrap();
+/// assert!(docs[0].as_hash().unwrap().keys().any(|k| k.is_comment()));
+/// ```
+#[derive(Clone)]
+pub struct LoaderOptions {
+    pub(crate) comments: bool,
+    pub(crate) multi_document: bool,
+    pub(crate) anchors: bool,
+    pub(crate) merge_keys: bool,
+    pub(crate) tags: bool,
+    pub(crate) schema: Schema,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) max_aliases: Option<usize>,
+    pub(crate) max_document_size: Option<usize>,
+    pub(crate) max_depth: usize,
+    pub(crate) constructors: HashMap<String, Constructor>,
+}
+
+impl Default for LoaderOptions {
+    fn default() -> LoaderOptions {
+        LoaderOptions {
+            comments: false,
+            multi_document: true,
+            anchors: false,
+            merge_keys: false,
+            tags: false,
+            schema: Schema::default(),
+            duplicate_keys: DuplicateKeys::default(),
+            max_nodes: None,
+            max_aliases: None,
+            max_document_size: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            constructors: HashMap::new(),
+        }
+    }
+}
+
+impl fmt::Debug for LoaderOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut constructors: Vec<_> = self.constructors.keys().collect();
+        constructors.sort();
+        f.debug_struct("LoaderOptions")
+            .field("comments", &self.comments)
+            .field("multi_document", &self.multi_document)
+            .field("anchors", &self.anchors)
+            .field("merge_keys", &self.merge_keys)
+            .field("tags", &self.tags)
+            .field("schema", &self.schema)
+            .field("duplicate_keys", &self.duplicate_keys)
+            .field("max_nodes", &self.max_nodes)
+            .field("max_aliases", &self.max_aliases)
+            .field("max_document_size", &self.max_document_size)
+            .field("max_depth", &self.max_depth)
+            .field("constructors", &constructors)
+            .finish()
+    }
+}
+
+impl LoaderOptions {
+    pub fn new() -> LoaderOptions {
+        LoaderOptions::default()
+    }
+
+    /// Keep comments in the loaded documents as `Yaml::Comment` nodes, so
+    /// they can be written back by the `YamlEmitter`.
+    pub fn comments(mut self, comments: bool) -> LoaderOptions {
+        self.comments = comments;
+        self
+    }
+
+    /// Load every document of the stream, or only the first one.
+    pub fn multi_document(mut self, multi_document: bool) -> LoaderOptions {
+        self.multi_document = multi_document;
+        self
+    }
+
+    /// Keep anchors and aliases in the loaded documents as `Yaml::Anchor` and
+    /// `Yaml::Alias` nodes instead of resolving aliases to copies of the
+    /// anchored nodes.
+    pub fn anchors(mut self, anchors: bool) -> LoaderOptions {
+        self.anchors = anchors;
+        self
+    }
+
+    /// Resolve merge keys (`<<: *defaults`) by merging the referenced
+    /// mappings into the mapping holding the key, as described in
+    /// [the merge key spec](https://yaml.org/type/merge.html).
+    ///
+    /// Merge keys are left as is when anchors are kept, as aliases are not
+    /// resolved in that case. They are loaded as `!!merge` tagged `<<` keys
+    /// then, which the `YamlEmitter` writes back as plain `<<` keys. A repeated
+    /// merge key is kept as one, e.g. `<<: *a` then `<<: *b` as
+    /// `<<: [*a, *b]`, which merges the same mappings.
+    ///
+    /// Without merge keys, `<<` keys are loaded as strings. The `YamlEmitter`
+    /// writes them plain when their value is an alias or a sequence of
+    /// aliases, and quoted otherwise.
+    pub fn merge_keys(mut self, merge_keys: bool) -> LoaderOptions {
+        self.merge_keys = merge_keys;
+        self
+    }
+
+    /// Keep tags the loader does not resolve by itself, such as `!Ref` or
+    /// `!!set`, as `Yaml::Tagged` nodes instead of dropping them.
+    pub fn tags(mut self, tags: bool) -> LoaderOptions {
+        self.tags = tags;
+        self
+    }
+
+    /// Resolve the untagged plain scalars, and the scalars tagged with a core
+    /// schema tag such as `!!bool`, of documents without a `%YAML` directive
+    /// with `schema`, e.g. `Schema::Yaml11` for legacy files where `yes` is a
+    /// boolean. Defaults to `Schema::Core`.
+    pub fn schema(mut self, schema: Schema) -> LoaderOptions {
+        self.schema = schema;
+        self
+    }
+
+    /// Choose what to do with keys appearing twice in a mapping. Defaults to
+    /// `DuplicateKeys::LastWins`, set `DuplicateKeys::Error` to report them.
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> LoaderOptions {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+
+    /// Fail to load documents with more than `max_nodes` nodes, counting
+    /// every node copied when resolving aliases. Bounds the memory used by
+    /// documents nesting aliases to expand exponentially, as in the "billion
+    /// laughs" attack. Unlimited by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::{LoaderOptions, YamlLoader};
+    ///
+    /// let s = "a: &a [x, x, x]\nb: &b [*a, *a, *a]\nc: [*b, *b, *b]\n";
+    /// assert!(YamlLoader::load_with_options(s, LoaderOptions::new().max_nodes(100)).is_ok());
+    /// assert!(YamlLoader::load_with_options(s, LoaderOptions::new().max_nodes(20)).is_err());
+    /// ```
+    pub fn max_nodes(mut self, max_nodes: usize) -> LoaderOptions {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Fail to load documents with more than `max_aliases` aliases. Unlimited
+    /// by default.
+    pub fn max_aliases(mut self, max_aliases: usize) -> LoaderOptions {
+        self.max_aliases = Some(max_aliases);
+        self
+    }
+
+    /// Fail to load documents spanning more than `max_document_size` bytes of
+    /// the source, counting from the end of the previous document. The
+    /// source is not read much further than the limit. Unlimited by default.
+    pub fn max_document_size(mut self, max_document_size: usize) -> LoaderOptions {
+        self.max_document_size = Some(max_document_size);
+        self
+    }
+
+    /// Fail to load documents nesting collections more than `max_depth`
+    /// levels deep. Defaults to `parser::DEFAULT_MAX_DEPTH`.
+    pub fn max_depth(mut self, max_depth: usize) -> LoaderOptions {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Register a constructor building the value of the nodes tagged with
+    /// `tag`, either in shorthand form with the default tag handles, e.g.
+    /// `!env` or `!!int`, or as a resolved tag, e.g.
+    /// `tag:example.com,2000:app/foo`. The scalars tagged with the core schema
+    /// tags, e.g. `!!int`, are resolved with the schema of their document
+    /// unless a constructor is registered for these tags.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust::{LoaderOptions, Yaml, YamlLoader};
+    ///
+    /// let options = LoaderOptions::new().constructor("!upper", |node: Yaml| match node {
+    ///     Yaml::String(v) => Ok(Yaml::String(v.to_uppercase())),
+    ///     _ => Err("!upper expects a scalar".to_owned()),
+    /// });
+    /// let docs = YamlLoader::load_with_options("a: !upper b", options.clone()).unwrap();
+    /// assert_eq!(docs[0]["a"].as_str().unwrap(), "B");
+    /// assert!(YamlLoader::load_with_options("a: !upper [b]", options).is_err());
+    /// ```
+    pub fn constructor<F>(mut self, tag: &str, constructor: F) -> LoaderOptions
+    where
+        F: Fn(Yaml) -> Result<Yaml, String> + Send + Sync + 'static,
+    {
+        let tag = match tag.strip_prefix("!<").and_then(|t| t.strip_suffix('>')) {
+            Some(verbatim) => tag_shorthand(verbatim),
+            None if tag.starts_with('!') => tag.to_owned(),
+            None => tag_shorthand(tag),
+        };
+        self.constructors.insert(tag, Arc::new(constructor));
+        self
+    }
+
+    /// Constructor of the nodes tagged with `tag`, in shorthand form, in a
+    /// document of `version`: the registered one, or else one resolving the
+    /// core schema scalars with the schema of the document.
+    pub(crate) fn constructor_for(
+        &self,
+        tag: &str,
+        version: Option<(u32, u32)>,
+    ) -> Option<Constructor> {
+        if let Some(constructor) = self.constructors.get(tag) {
+            return Some(constructor.clone());
+        }
+        if !CORE_SCALAR_TAGS.contains(&tag) {
+            return None;
+        }
+        let (tag, schema) = (tag.to_owned(), self.schema.for_version(version));
+        Some(Arc::new(move |node| Ok(construct_core(&tag, node, schema))))
+    }
+
+    /// Determine if comments are kept in the loaded documents.
+    pub fn has_comments(&self) -> bool {
+        self.comments
+    }
+
+    /// Determine if every document of the stream is loaded.
+    pub fn is_multi_document(&self) -> bool {
+        self.multi_document
+    }
+
+    /// Determine if anchors and aliases are kept in the loaded documents.
+    pub fn has_anchors(&self) -> bool {
+        self.anchors
+    }
+
+    /// Determine if merge keys are resolved.
+    pub fn has_merge_keys(&self) -> bool {
+        self.merge_keys
+    }
+
+    /// Determine if tags unknown to the loader are kept.
+    pub fn has_tags(&self) -> bool {
+        self.tags
+    }
+
+    /// Get the schema resolving untagged plain scalars.
+    pub fn get_schema(&self) -> Schema {
+        self.schema
+    }
+
+    /// Get what is done with keys appearing twice in a mapping.
+    pub fn get_duplicate_keys(&self) -> DuplicateKeys {
+        self.duplicate_keys
+    }
+
+    /// Get the maximum number of nodes of a document, if limited.
+    pub fn get_max_nodes(&self) -> Option<usize> {
+        self.max_nodes
+    }
+
+    /// Get the maximum number of aliases of a document, if limited.
+    pub fn get_max_aliases(&self) -> Option<usize> {
+        self.max_aliases
+    }
+
+    /// Get the maximum size of a document in bytes, if limited.
+    pub fn get_max_document_size(&self) -> Option<usize> {
+        self.max_document_size
+    }
+
+    /// Get the maximum nesting depth of collections.
+    pub fn get_max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
@@ -0,0 +1,195 @@
+use super::Yaml;
+
+/// Rules used by `YamlLoader` to resolve the type of untagged plain scalars.
+///
+/// With `Schema::Core` or `Schema::Yaml11`, documents starting with a
+/// `%YAML 1.1` or `%YAML 1.2` directive are resolved with the schema of their
+/// version.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Schema {
+    /// The YAML 1.2 core schema, see `Yaml::from_str`.
+    #[default]
+    Core,
+    /// The YAML 1.1 types, see `Yaml::from_str_1_1`.
+    Yaml11,
+    /// The YAML 1.2 JSON schema: only `null`, `true`, `false` and JSON
+    /// numbers are resolved, e.g. `~`, `NO` or `0x1F` are strings.
+    Json,
+    /// The YAML 1.2 failsafe schema: every plain scalar is a string.
+    Failsafe,
+}
+
+impl Schema {
+    /// Resolve the plain scalar `v`, falling back to `Yaml::String`.
+    pub fn resolve(self, v: &str) -> Yaml {
+        match self {
+            Schema::Core => Yaml::from_str(v),
+            Schema::Yaml11 => Yaml::from_str_1_1(v),
+            Schema::Json => resolve_json(v),
+            Schema::Failsafe => Yaml::String(v.to_owned()),
+        }
+    }
+
+    // schema resolving the plain scalars of a document with the given %YAML
+    // directive version
+    pub(crate) fn for_version(self, version: Option<(u32, u32)>) -> Schema {
+        match (self, version) {
+            (Schema::Core, Some((1, minor))) | (Schema::Yaml11, Some((1, minor))) if minor < 2 => {
+                Schema::Yaml11
+            }
+            (Schema::Core, Some(_)) | (Schema::Yaml11, Some(_)) => Schema::Core,
+            (schema, _) => schema,
+        }
+    }
+}
+
+fn resolve_json(v: &str) -> Yaml {
+    match v {
+        "null" => Yaml::Null,
+        "true" => Yaml::Boolean(true),
+        "false" => Yaml::Boolean(false),
+        _ if is_json_number(v, false) => match v.parse::<i64>() {
+            Ok(i) => Yaml::Integer(i),
+            // out of range integers are kept as reals, as in JSON
+            Err(_) => Yaml::Real(v.to_owned()),
+        },
+        _ if is_json_number(v, true) => Yaml::Real(v.to_owned()),
+        _ => Yaml::String(v.to_owned()),
+    }
+}
+
+// `-?(0|[1-9][0-9]*)`, followed by `(\.[0-9]*)?([eE][-+]?[0-9]+)?` if `real`
+fn is_json_number(v: &str, real: bool) -> bool {
+    let v = v.strip_prefix('-').unwrap_or(v);
+    let int_len = v.find(|c: char| !c.is_ascii_digit()).unwrap_or(v.len());
+    let (int, rest) = v.split_at(int_len);
+    if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+        return false;
+    }
+    if !real {
+        return rest.is_empty();
+    }
+    let rest = match rest.strip_prefix('.') {
+        Some(fraction) => fraction.trim_start_matches(|c: char| c.is_ascii_digit()),
+        None => rest,
+    };
+    match rest.strip_prefix(['e', 'E']) {
+        Some(exponent) => {
+            let digits = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+        }
+        None => rest.is_empty(),
+    }
+}
+
+fn split_sign(v: &str) -> (bool, &str) {
+    match v.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, v.strip_prefix('+').unwrap_or(v)),
+    }
+}
+
+// digits of the given radix, possibly separated by `_`
+fn parse_radix(v: &str, radix: u32) -> Option<i64> {
+    if !v.chars().all(|c| c == '_' || c.is_digit(radix)) {
+        return None;
+    }
+    let digits: String = v.chars().filter(|&c| c != '_').collect();
+    if digits.is_empty() {
+        return None;
+    }
+    i64::from_str_radix(&digits, radix).ok()
+}
+
+// `[0-9][0-9_]*(:[0-5]?[0-9])+`, without the leading sign
+fn parse_sexagesimal(v: &str) -> Option<i64> {
+    let mut parts = v.split(':');
+    let first = parts.next()?;
+    if !first.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut value = parse_radix(first, 10)?;
+    for part in parts {
+        let digit = match part.as_bytes() {
+            [d @ b'0'..=b'9'] => d - b'0',
+            [t @ b'0'..=b'5', d @ b'0'..=b'9'] => (t - b'0') * 10 + (d - b'0'),
+            _ => return None,
+        };
+        value = value.checked_mul(60)?.checked_add(i64::from(digit))?;
+    }
+    Some(value)
+}
+
+/// Parse an integer as in [YAML 1.1](https://yaml.org/type/int.html):
+/// `0b1010`, `0777`, `1_000`, `0x1F` or `1:30`.
+pub(crate) fn parse_int_1_1(v: &str) -> Option<i64> {
+    let (negative, digits) = split_sign(v);
+    let value = if let Some(bin) = digits.strip_prefix("0b") {
+        parse_radix(bin, 2)?
+    } else if let Some(hex) = digits.strip_prefix("0x") {
+        parse_radix(hex, 16)?
+    } else if digits.contains(':') {
+        if digits.starts_with('0') {
+            return None;
+        }
+        parse_sexagesimal(digits)?
+    } else if digits == "0" {
+        0
+    } else if let Some(oct) = digits.strip_prefix('0') {
+        parse_radix(oct, 8)?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        parse_radix(digits, 10)?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Parse a float as in [YAML 1.1](https://yaml.org/type/float.html):
+/// `1_000.5`, `6.8523015e+5`, `190:20:30.15` or `.inf`. Returns the value
+/// written so that `Yaml::as_f64` understands it.
+pub(crate) fn parse_float_1_1(v: &str) -> Option<String> {
+    match v {
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => return Some(".inf".to_owned()),
+        "-.inf" | "-.Inf" | "-.INF" => return Some("-.inf".to_owned()),
+        ".nan" | ".NaN" | ".NAN" => return Some(".nan".to_owned()),
+        _ => {}
+    }
+    let (negative, rest) = split_sign(v);
+    let (int, fraction) = rest.split_at(rest.find('.')?);
+    let sign = if negative { "-" } else { "" };
+    if int.contains(':') {
+        // base 60, `[0-9][0-9_]*(:[0-5]?[0-9])+\.[0-9_]*`
+        if !fraction[1..]
+            .chars()
+            .all(|c| c == '_' || c.is_ascii_digit())
+        {
+            return None;
+        }
+        let fraction: String = fraction.chars().filter(|&c| c != '_').collect();
+        let value =
+            parse_sexagesimal(int)? as f64 + format!("0{}", fraction).parse::<f64>().ok()?;
+        return Some(format!("{}{:?}", sign, value));
+    }
+    // base 10, `([0-9][0-9_]*)?\.[0-9.]*([eE][-+][0-9]+)?`
+    if !(int.is_empty() || int.starts_with(|c: char| c.is_ascii_digit()))
+        || !int.chars().all(|c| c == '_' || c.is_ascii_digit())
+    {
+        return None;
+    }
+    let (fraction, exponent) = match fraction.find(['e', 'E']) {
+        Some(i) => fraction.split_at(i),
+        None => (fraction, ""),
+    };
+    if !fraction.chars().all(|c| c == '.' || c.is_ascii_digit()) {
+        return None;
+    }
+    if !exponent.is_empty() {
+        let digits = exponent[1..].strip_prefix(['-', '+'])?;
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    }
+    let value = format!("{}{}{}{}", sign, int.replace('_', ""), fraction, exponent);
+    value.parse::<f64>().ok().map(|_| value)
+}
@@ -30,7 +30,7 @@ struct YamlChecker {
 impl EventReceiver for YamlChecker {
     fn on_event(&mut self, ev: Event, _mark: Marker) {
         let tev = match ev {
-            Event::DocumentStart => TestEvent::OnDocumentStart,
+            Event::DocumentStart(_) => TestEvent::OnDocumentStart,
             Event::DocumentEnd => TestEvent::OnDocumentEnd,
             Event::SequenceStart(..) => TestEvent::OnSequenceStart,
             Event::SequenceEnd => TestEvent::OnSequenceEnd,